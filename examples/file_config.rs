@@ -11,10 +11,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("Loaded configuration:");
     println!("  Mode: {:?}", config.mode);
-    println!(
-        "  Remote: {}@{}:{}",
-        config.remote.user, config.remote.host, config.remote.port
-    );
+    for name in config.remote_names() {
+        if let Some(remote) = config.remote_config(&name) {
+            println!(
+                "  Remote {}: {}@{}:{}",
+                name, remote.user, remote.host, remote.port
+            );
+        }
+    }
     println!("  Forwarding {} tunnels", config.forwarding_list.len());
 
     // Create tunnel manager
@@ -1,4 +1,7 @@
-use stun::{Config, ForwardingMode, RemoteConfig, TunnelManager};
+use stun::{
+    Config, FailoverPolicy, ForwardingMode, HostKeyPolicy, RemoteConfig, TransportKind,
+    TunnelManager,
+};
 
 /// Example: Create configuration programmatically and start tunneling
 #[tokio::main]
@@ -14,11 +17,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             port: 22,
             user: "username".to_string(),
             key: Some("~/.ssh/id_rsa".to_string()),
+            multiplex: false,
+            transport: TransportKind::Process,
+            host_key_policy: HostKeyPolicy::AcceptNew,
+            known_hosts: None,
+            fallbacks: Vec::new(),
+            failover: FailoverPolicy::Ordered,
+            jump_hosts: Vec::new(),
         },
+        remotes: Default::default(),
+        include: Vec::new(),
         forwarding_list: vec![
-            "8080:127.0.0.1:8080".to_string(),
-            "3306:database.internal:3306".to_string(),
-            "5432:postgres.internal:5432".to_string(),
+            "8080:127.0.0.1:8080".into(),
+            "3306:database.internal:3306".into(),
+            "5432:postgres.internal:5432".into(),
         ],
         timeout: Some(5),
         remote_probes: None,
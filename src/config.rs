@@ -1,21 +1,37 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-use crate::error::{StunError, StunResult};
+use crate::{
+    error::{StunError, StunResult},
+    forwarding::{ForwardingSpec, format_host, format_host_port, parse_host_port},
+    validation::{ValidationIssue, ValidationReport},
+};
 
 /// Configuration for the SSH tunneling
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     /// Forwarding mode: local or remote
     pub mode: ForwardingMode,
-    /// Remote SSH server configuration
+    /// Remote SSH server for tunnels that do not name one
+    #[serde(default, skip_serializing_if = "RemoteConfig::is_unset")]
     pub remote: RemoteConfig,
-    /// List of port forwarding specifications
-    pub forwarding_list: Vec<String>,
+    /// Additional SSH servers by name, selected per tunnel with its `remote` field
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub remotes: BTreeMap<String, RemoteConfig>,
+    /// Config fragments whose `forwarding_list` and `remote_probes` are merged into
+    /// this one, relative to the including file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Tunnels to forward, as spec strings or object entries
+    pub forwarding_list: Vec<ForwardingEntry>,
     /// Connection timeout in seconds
     pub timeout: Option<u64>,
-    /// Optional mapping for remote mode health probing: spec_string -> "host:port"
+    /// Optional mapping for health probing: tunnel name -> "host:port"
     #[serde(default)]
     pub remote_probes: Option<HashMap<String, String>>,
     /// Base backoff seconds for restart scheduling (optional, default: 1)
@@ -24,12 +40,339 @@ pub struct Config {
     pub backoff_max_secs: Option<u64>,
 }
 
+/// Entry of `forwarding_list`: a spec string or an object with per-tunnel options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ForwardingEntry {
+    /// Shorthand such as `"8080:127.0.0.1:80"` or `"R:9000:127.0.0.1:9000"`
+    Spec(String),
+    /// Object form, e.g. `{"name": "db", "bind": "5432", "target": "db.internal:5432"}`
+    Tunnel(TunnelEntry),
+}
+
+impl From<&str> for ForwardingEntry {
+    fn from(spec: &str) -> Self {
+        ForwardingEntry::Spec(spec.to_string())
+    }
+}
+
+impl From<String> for ForwardingEntry {
+    fn from(spec: String) -> Self {
+        ForwardingEntry::Spec(spec)
+    }
+}
+
+/// File listed in `include`, contributing tunnels and probes to the including config
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFragment {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    forwarding_list: Vec<ForwardingEntry>,
+    #[serde(default)]
+    remote_probes: Option<HashMap<String, String>>,
+}
+
+/// Object form of a forwarding entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TunnelEntry {
+    /// Tunnel name used in logs, status and `remote_probes` (default: the spec string)
+    pub name: Option<String>,
+    /// Bind side: `[bind_addr:]port`
+    pub bind: String,
+    /// Target `host:port`; omitted for dynamic forwards
+    pub target: Option<String>,
+    /// Direction of this tunnel (default: the top-level `mode`)
+    pub mode: Option<ForwardingMode>,
+    /// Name of the entry in `remotes` to connect through (default: the top-level `remote`)
+    pub remote: Option<String>,
+    /// `host:port` to probe during health checks, like a `remote_probes` entry
+    pub probe: Option<String>,
+    /// Health check timeout in seconds (default: the top-level `timeout`)
+    pub timeout: Option<u64>,
+    /// Disabled tunnels are loaded paused and can be resumed at runtime
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Free-form labels reported in status
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A forwarding entry resolved against the top-level settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct TunnelConfig {
+    /// Key the tunnel is tracked under
+    pub name: String,
+    /// Forwarding specification
+    pub spec: ForwardingSpec,
+    /// Remote the tunnel connects through, [`DEFAULT_REMOTE`] for the top-level `remote`
+    pub remote: String,
+    /// Health probe target from the entry or `remote_probes`
+    pub probe: Option<(String, u16)>,
+    /// Health check timeout in seconds, overriding the top-level `timeout`
+    pub timeout: Option<u64>,
+    /// Whether the tunnel starts running
+    pub enabled: bool,
+    /// Labels reported in status
+    pub tags: Vec<String>,
+    /// Name of the port range entry this tunnel was expanded from
+    pub group: Option<String>,
+}
+
+impl TunnelConfig {
+    /// An enabled tunnel named after its spec string, without per-tunnel options
+    pub fn from_spec(spec: ForwardingSpec) -> Self {
+        Self {
+            name: spec.to_spec_string(),
+            spec,
+            remote: DEFAULT_REMOTE.to_string(),
+            probe: None,
+            timeout: None,
+            enabled: true,
+            tags: Vec::new(),
+            group: None,
+        }
+    }
+}
+
+/// File format of a configuration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    #[default]
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Format implied by the file extension: `.toml`, `.yaml`/`.yml`, otherwise JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                ConfigFormat::Yaml
+            }
+            _ => ConfigFormat::Json,
+        }
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = StunError;
+
+    fn from_str(s: &str) -> StunResult<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ConfigFormat::Json),
+            "toml" => Ok(ConfigFormat::Toml),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            _ => Err(StunError::Config(format!(
+                "Unknown config format '{s}', expected json, toml or yaml"
+            ))),
+        }
+    }
+}
+
+/// Configuration file together with how to read it, so it can be loaded again on reload
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigSource {
+    /// Path of the configuration file
+    pub path: PathBuf,
+    /// Explicit format; None detects it from the extension
+    pub format: Option<ConfigFormat>,
+    /// Values that take precedence over the file
+    pub overrides: ConfigOverrides,
+}
+
+impl ConfigSource {
+    /// Source whose format follows the file extension
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            format: None,
+            overrides: ConfigOverrides::default(),
+        }
+    }
+
+    /// Read the file as `format` regardless of its extension
+    pub fn with_format(mut self, format: ConfigFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Apply `overrides` on top of the file every time it is loaded
+    pub fn with_overrides(mut self, overrides: ConfigOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Format the file is read in
+    pub fn format(&self) -> ConfigFormat {
+        self.format
+            .unwrap_or_else(|| ConfigFormat::from_path(&self.path))
+    }
+
+    /// Load the file, apply the overrides and validate the result
+    pub fn load(&self) -> StunResult<Config> {
+        let (config, report) = self.report();
+        report.into_result()?;
+        Ok(config.expect("a report without errors comes with a configuration"))
+    }
+
+    /// Load like [`load`](Self::load), but collect every problem instead of failing.
+    /// The configuration is None when the file could not be read or parsed.
+    pub fn report(&self) -> (Option<Config>, ValidationReport) {
+        let (mut config, mut origins) = match Config::read(&self.path, self.format()) {
+            Ok(read) => read,
+            Err(issue) => {
+                return (
+                    None,
+                    ValidationReport {
+                        issues: vec![issue],
+                    },
+                );
+            }
+        };
+        self.overrides.apply(&mut config);
+        if !self.overrides.forwards.is_empty() {
+            origins.clear();
+        }
+        let report = config.report_with_origins(&origins);
+        (Some(config), report)
+    }
+}
+
+/// Settings layered over a configuration file, from `STUN_*` variables or CLI flags
+///
+/// Remote settings apply to the top-level `remote`. Forwards replace the file's
+/// `forwarding_list` rather than extending it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides {
+    /// Forwarding mode (`STUN_MODE`, `--mode`)
+    pub mode: Option<ForwardingMode>,
+    /// Remote SSH host (`STUN_HOST`, `--host`)
+    pub host: Option<String>,
+    /// Remote SSH port (`STUN_PORT`, `--port`)
+    pub port: Option<u16>,
+    /// Remote SSH user (`STUN_USER`, `--user`)
+    pub user: Option<String>,
+    /// Private key path (`STUN_KEY`, `--key`)
+    pub key: Option<String>,
+    /// Forwarding specs that replace `forwarding_list` (`STUN_FORWARD`, `--forward`)
+    pub forwards: Vec<String>,
+    /// Connection timeout in seconds (`STUN_TIMEOUT`, `--timeout`)
+    pub timeout: Option<u64>,
+}
+
+impl ConfigOverrides {
+    /// Overrides from the process environment
+    pub fn from_env() -> StunResult<Self> {
+        Self::from_vars(
+            std::env::vars_os().filter_map(|(key, value)| {
+                Some((key.into_string().ok()?, value.into_string().ok()?))
+            }),
+        )
+    }
+
+    /// Overrides from `STUN_MODE`, `STUN_HOST`, `STUN_PORT`, `STUN_USER`, `STUN_KEY`,
+    /// `STUN_TIMEOUT` and `STUN_FORWARD` (comma-separated specs); other variables are ignored
+    pub fn from_vars<I, K, V>(vars: I) -> StunResult<Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut overrides = Self::default();
+        for (key, value) in vars {
+            let (key, value) = (key.as_ref(), value.as_ref().trim());
+            match key {
+                "STUN_MODE" => overrides.mode = Some(value.parse()?),
+                "STUN_HOST" => overrides.host = Some(value.to_string()),
+                "STUN_PORT" => overrides.port = Some(parse_var(key, value)?),
+                "STUN_USER" => overrides.user = Some(value.to_string()),
+                "STUN_KEY" => overrides.key = Some(value.to_string()),
+                "STUN_TIMEOUT" => overrides.timeout = Some(parse_var(key, value)?),
+                "STUN_FORWARD" => {
+                    overrides.forwards = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|spec| !spec.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// Combine two layers, letting values set in `other` win
+    pub fn merge(self, other: ConfigOverrides) -> Self {
+        Self {
+            mode: other.mode.or(self.mode),
+            host: other.host.or(self.host),
+            port: other.port.or(self.port),
+            user: other.user.or(self.user),
+            key: other.key.or(self.key),
+            forwards: if other.forwards.is_empty() {
+                self.forwards
+            } else {
+                other.forwards
+            },
+            timeout: other.timeout.or(self.timeout),
+        }
+    }
+
+    /// Whether no value is overridden
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Write the overridden values into `config`
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(mode) = self.mode {
+            config.mode = mode;
+        }
+        if let Some(host) = &self.host {
+            config.remote.host = host.clone();
+        }
+        if let Some(port) = self.port {
+            config.remote.port = port;
+        }
+        if let Some(user) = &self.user {
+            config.remote.user = user.clone();
+        }
+        if let Some(key) = &self.key {
+            config.remote.key = Some(key.clone());
+        }
+        if !self.forwards.is_empty() {
+            config.forwarding_list = self
+                .forwards
+                .iter()
+                .map(|spec| spec.as_str().into())
+                .collect();
+        }
+        if let Some(timeout) = self.timeout {
+            config.timeout = Some(timeout);
+        }
+    }
+}
+
+fn parse_var<T: FromStr>(key: &str, value: &str) -> StunResult<T> {
+    value
+        .parse()
+        .map_err(|_| StunError::Config(format!("Invalid {key} '{value}'")))
+}
+
 /// Forwarding mode enumeration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ForwardingMode {
+    #[default]
     Local,
     Remote,
+    /// SOCKS proxy on the local side (`ssh -D`)
+    Dynamic,
 }
 
 impl ForwardingMode {
@@ -38,12 +381,37 @@ impl ForwardingMode {
         match self {
             ForwardingMode::Local => "-L",
             ForwardingMode::Remote => "-R",
+            ForwardingMode::Dynamic => "-D",
+        }
+    }
+
+    /// Prefix that selects this mode in a forwarding spec string
+    pub fn spec_prefix(&self) -> &'static str {
+        match self {
+            ForwardingMode::Local => "L:",
+            ForwardingMode::Remote => "R:",
+            ForwardingMode::Dynamic => "D:",
+        }
+    }
+}
+
+impl FromStr for ForwardingMode {
+    type Err = StunError;
+
+    fn from_str(s: &str) -> StunResult<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "local" => Ok(ForwardingMode::Local),
+            "remote" => Ok(ForwardingMode::Remote),
+            "dynamic" => Ok(ForwardingMode::Dynamic),
+            _ => Err(StunError::Config(format!(
+                "Unknown forwarding mode '{s}', expected local, remote or dynamic"
+            ))),
         }
     }
 }
 
 /// Remote SSH server configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RemoteConfig {
     /// Hostname or IP address
     pub host: String,
@@ -54,29 +422,482 @@ pub struct RemoteConfig {
     pub user: String,
     /// Path to private key file (optional)
     pub key: Option<String>,
+    /// Carry every forward over one shared SSH connection (OpenSSH ControlMaster)
+    #[serde(default)]
+    pub multiplex: bool,
+    /// Backend used to establish forwards (default: process)
+    #[serde(default)]
+    pub transport: TransportKind,
+    /// How the server's host key is verified (default: accept-new)
+    #[serde(default)]
+    pub host_key_policy: HostKeyPolicy,
+    /// Path to the known_hosts file (default: ~/.ssh/known_hosts)
+    pub known_hosts: Option<String>,
+    /// Alternative endpoints of the same server, tried after `host`/`port` in order
+    #[serde(default)]
+    pub fallbacks: Vec<Endpoint>,
+    /// When to move on to the next endpoint (default: ordered)
+    #[serde(default)]
+    pub failover: FailoverPolicy,
+    /// Hosts to hop through on the way to the remote, first hop first
+    #[serde(default)]
+    pub jump_hosts: Vec<JumpHost>,
+}
+
+/// Intermediate SSH server the connection to a remote is relayed through
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JumpHost {
+    /// Hostname or IP address
+    pub host: String,
+    /// SSH port (default: 22)
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+    /// Username on the jump host (default: ssh's own default)
+    pub user: Option<String>,
+    /// Path to the private key for the jump host (optional)
+    pub key: Option<String>,
+}
+
+impl JumpHost {
+    /// `[user@]host[:port]` as accepted by `ssh -J`
+    pub fn destination(&self) -> String {
+        let host = format_host(&self.host);
+        let mut destination = match &self.user {
+            Some(user) => format!("{user}@{host}"),
+            None => host.into_owned(),
+        };
+        if self.port != 22 {
+            destination.push_str(&format!(":{}", self.port));
+        }
+        destination
+    }
+}
+
+/// Address at which an SSH server can be reached
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Endpoint {
+    /// Hostname or IP address
+    pub host: String,
+    /// SSH port (default: 22)
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", format_host_port(&self.host, self.port))
+    }
+}
+
+/// Failover behaviour of a remote with `fallbacks`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailoverPolicy {
+    /// Stay on an endpoint until forwards fail to start repeatedly, then move down
+    /// the list, wrapping around to the primary after the last fallback
+    #[default]
+    Ordered,
+    /// Move to the next endpoint after every failed start
+    RoundRobin,
+}
+
+/// Host key verification policy
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HostKeyPolicy {
+    /// Only connect to hosts with a matching known_hosts entry
+    Strict,
+    /// Record unknown hosts on first connection, reject changed keys
+    #[default]
+    AcceptNew,
+    /// Only accept keys with one of these SHA256 fingerprints, e.g. `SHA256:nThbg6kXUpJWGl7E1IGOCspRomTxdCARLviKw6E5SY8`
+    Pinned(Vec<String>),
+}
+
+/// SSH backend selection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    /// Spawn the system `ssh` binary
+    #[default]
+    Process,
+    /// In-process SSH client (requires the `native-ssh` feature)
+    Native,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: default_ssh_port(),
+            user: String::new(),
+            key: None,
+            multiplex: false,
+            transport: TransportKind::default(),
+            host_key_policy: HostKeyPolicy::default(),
+            known_hosts: None,
+            fallbacks: Vec::new(),
+            failover: FailoverPolicy::default(),
+            jump_hosts: Vec::new(),
+        }
+    }
+}
+
+impl RemoteConfig {
+    /// Whether the remote was left out of the configuration
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The primary `host`/`port` followed by the fallbacks
+    pub fn endpoints(&self) -> Vec<Endpoint> {
+        let primary = Endpoint {
+            host: self.host.clone(),
+            port: self.port,
+        };
+        std::iter::once(primary)
+            .chain(self.fallbacks.iter().cloned())
+            .collect()
+    }
+
+    /// Copy of this remote that connects to `endpoint` instead of `host`/`port`
+    pub fn at_endpoint(&self, endpoint: &Endpoint) -> RemoteConfig {
+        RemoteConfig {
+            host: endpoint.host.clone(),
+            port: endpoint.port,
+            ..self.clone()
+        }
+    }
+
+    /// Expand variables in host names and users and `~` in key paths; `field` is
+    /// the remote's location in the file, used in errors
+    fn expand(&mut self, field: &str, lookup: &dyn Fn(&str) -> Option<String>) -> StunResult<()> {
+        self.host = expand_value(&self.host, &format!("{field}.host"), lookup)?;
+        self.user = expand_value(&self.user, &format!("{field}.user"), lookup)?;
+        if let Some(key) = &mut self.key {
+            *key = expand_path(key, &format!("{field}.key"), lookup)?;
+        }
+        if let Some(known_hosts) = &mut self.known_hosts {
+            *known_hosts = expand_path(known_hosts, &format!("{field}.known_hosts"), lookup)?;
+        }
+        for (index, fallback) in self.fallbacks.iter_mut().enumerate() {
+            let field = format!("{field}.fallbacks[{index}].host");
+            fallback.host = expand_value(&fallback.host, &field, lookup)?;
+        }
+        for (index, jump) in self.jump_hosts.iter_mut().enumerate() {
+            let field = format!("{field}.jump_hosts[{index}]");
+            jump.host = expand_value(&jump.host, &format!("{field}.host"), lookup)?;
+            if let Some(user) = &mut jump.user {
+                *user = expand_value(user, &format!("{field}.user"), lookup)?;
+            }
+            if let Some(key) = &mut jump.key {
+                *key = expand_path(key, &format!("{field}.key"), lookup)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the problems of the remote found at `path` in the document
+    fn check(&self, name: &str, path: &str, report: &mut ValidationReport) {
+        // The top-level remote keeps its historical unqualified messages
+        let label = if name == DEFAULT_REMOTE {
+            "Remote".to_string()
+        } else {
+            format!("Remote '{name}'")
+        };
+
+        if self.host.is_empty() {
+            report.error(
+                format!("{path}.host"),
+                format!("{label} host cannot be empty"),
+            );
+        }
+
+        if self.user.is_empty() {
+            report.error(
+                format!("{path}.user"),
+                format!("{label} user cannot be empty"),
+            );
+        }
+
+        for (index, endpoint) in self.fallbacks.iter().enumerate() {
+            if endpoint.host.is_empty() {
+                report.error(
+                    format!("{path}.fallbacks[{index}].host"),
+                    format!("{label} fallback host cannot be empty"),
+                );
+            }
+        }
+
+        for (index, jump) in self.jump_hosts.iter().enumerate() {
+            if jump.host.is_empty() {
+                report.error(
+                    format!("{path}.jump_hosts[{index}].host"),
+                    format!("{label} jump host cannot be empty"),
+                );
+            }
+        }
+
+        if self.transport == TransportKind::Native && !cfg!(feature = "native-ssh") {
+            report.error(
+                format!("{path}.transport"),
+                "transport 'native' requires building stun with the `native-ssh` feature",
+            );
+        }
+
+        if self.transport == TransportKind::Native && !self.jump_hosts.is_empty() {
+            report.error(
+                format!("{path}.jump_hosts"),
+                format!("{label}: jump_hosts require the process transport"),
+            );
+        }
+
+        if let HostKeyPolicy::Pinned(fingerprints) = &self.host_key_policy {
+            let policy_path = format!("{path}.host_key_policy");
+            if fingerprints.is_empty() {
+                report.error(
+                    &policy_path,
+                    format!("{label}: pinned host key policy needs at least one fingerprint"),
+                );
+            }
+            for (index, fingerprint) in fingerprints.iter().enumerate() {
+                if !fingerprint.starts_with("SHA256:") {
+                    report.error(
+                        format!("{policy_path}.pinned[{index}]"),
+                        format!(
+                            "Invalid host key fingerprint '{fingerprint}', expected SHA256:<base64>"
+                        ),
+                    );
+                }
+            }
+            // Keys are fetched with ssh-keyscan, which cannot hop through jump hosts
+            if !self.jump_hosts.is_empty() {
+                report.error(
+                    &policy_path,
+                    format!("{label}: pinned host keys cannot be combined with jump_hosts"),
+                );
+            }
+        }
+    }
 }
 
+/// Name under which the top-level `remote` is addressed
+pub const DEFAULT_REMOTE: &str = "default";
+
 fn default_ssh_port() -> u16 {
     22
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
 impl Config {
-    /// Load configuration from a JSON file
+    /// Load configuration from a JSON, TOML or YAML file, chosen by its extension
     pub fn from_file<P: AsRef<Path>>(path: P) -> StunResult<Self> {
+        let format = ConfigFormat::from_path(path.as_ref());
+        Self::from_file_with_format(path, format)
+    }
+
+    /// Load configuration from a file in the given format, whatever its extension
+    pub fn from_file_with_format<P: AsRef<Path>>(
+        path: P,
+        format: ConfigFormat,
+    ) -> StunResult<Self> {
+        let (config, origins) =
+            Self::read(path.as_ref(), format).map_err(|e| StunError::Config(e.to_string()))?;
+        config.report_with_origins(&origins).into_result()?;
+        Ok(config)
+    }
+
+    /// Read a file with its includes merged and values expanded, without validating it.
+    /// Also returns the file each `forwarding_list` entry came from.
+    fn read(path: &Path, format: ConfigFormat) -> Result<(Self, Vec<String>), ValidationIssue> {
         let content = std::fs::read_to_string(path)
-            .map_err(|e| StunError::Config(format!("Failed to read config file: {e}")))?;
+            .map_err(|e| ValidationIssue::error("", format!("Failed to read config file: {e}")))?;
 
-        let config: Config = serde_json::from_str(&content)
-            .map_err(|e| StunError::Config(format!("Failed to parse config: {e}")))?;
+        let mut config: Self = deserialize(&content, format)?;
+        let lookup = |name: &str| std::env::var(name).ok();
+        config
+            .expand_with(&lookup)
+            .and_then(|()| config.merge_includes(path, &lookup))
+            .map(|origins| (config, origins))
+            .map_err(|e| match e {
+                StunError::Config(message) => ValidationIssue::error("", message),
+                other => ValidationIssue::error("", other.to_string()),
+            })
+    }
 
-        config.validate()?;
-        Ok(config)
+    /// Append the tunnels and probes of every included fragment, recursively, and
+    /// clear `include`. Returns the file each `forwarding_list` entry came from.
+    fn merge_includes(
+        &mut self,
+        path: &Path,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> StunResult<Vec<String>> {
+        let mut origins = vec![path.display().to_string(); self.forwarding_list.len()];
+        let includes = std::mem::take(&mut self.include);
+        let mut chain = vec![path.canonicalize()?];
+        self.merge_fragments(&includes, path, &mut origins, &mut chain, lookup)?;
+        Ok(origins)
+    }
+
+    fn merge_fragments(
+        &mut self,
+        includes: &[String],
+        from: &Path,
+        origins: &mut Vec<String>,
+        chain: &mut Vec<PathBuf>,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> StunResult<()> {
+        for include in includes {
+            let include = expand_path(include, &format!("include of {}", from.display()), lookup)?;
+            let path = from.parent().unwrap_or(Path::new("")).join(include);
+            let read_error = |e: std::io::Error| {
+                StunError::Config(format!(
+                    "Failed to read {} included from {}: {e}",
+                    path.display(),
+                    from.display()
+                ))
+            };
+            let canonical = path.canonicalize().map_err(read_error)?;
+            if chain.contains(&canonical) {
+                return Err(StunError::Config(format!(
+                    "Include cycle: {} includes {}",
+                    from.display(),
+                    path.display()
+                )));
+            }
+
+            let content = std::fs::read_to_string(&path).map_err(read_error)?;
+            let mut fragment: ConfigFragment =
+                deserialize(&content, ConfigFormat::from_path(&path))
+                    .map_err(|issue| StunError::Config(format!("{}: {issue}", path.display())))?;
+            let origin = path.display().to_string();
+            expand_entries(
+                &mut fragment.forwarding_list,
+                &format!("{origin}: forwarding_list"),
+                lookup,
+            )?;
+
+            origins.extend(std::iter::repeat_n(
+                origin.clone(),
+                fragment.forwarding_list.len(),
+            ));
+            self.forwarding_list.append(&mut fragment.forwarding_list);
+            for (tunnel, target) in fragment.remote_probes.unwrap_or_default() {
+                let probes = self.remote_probes.get_or_insert_with(HashMap::new);
+                match probes.get(&tunnel) {
+                    Some(existing) if *existing != target => {
+                        return Err(StunError::Config(format!(
+                            "Probe for '{tunnel}' in {origin} conflicts with '{existing}' set elsewhere"
+                        )));
+                    }
+                    _ => {
+                        probes.insert(tunnel, target);
+                    }
+                }
+            }
+
+            chain.push(canonical);
+            self.merge_fragments(&fragment.include, &path, origins, chain, lookup)?;
+            chain.pop();
+        }
+        Ok(())
+    }
+
+    /// Report tunnels that would listen on the same address and port. Local and
+    /// dynamic forwards bind on this host, remote forwards on their SSH server.
+    fn check_duplicate_binds(
+        &self,
+        tunnels: &[(usize, TunnelConfig)],
+        origins: &[String],
+        report: &mut ValidationReport,
+    ) {
+        // Files are only named when the two tunnels come from different ones
+        let in_origin = |index: usize, other: usize| match (origins.get(index), origins.get(other))
+        {
+            (Some(origin), Some(other)) if origin != other => format!(" in {origin}"),
+            _ => String::new(),
+        };
+        let mut bound: Vec<(usize, &TunnelConfig, String)> = Vec::new();
+
+        for (index, tunnel) in tunnels {
+            let side = match tunnel.spec.resolve_mode(self.mode) {
+                Ok(ForwardingMode::Remote) => tunnel.remote.clone(),
+                Ok(_) => String::new(),
+                Err(_) => continue,
+            };
+
+            if let Some((other_index, other, _)) = bound.iter().find(|(_, other, other_side)| {
+                *other_side == side && binds_overlap(&other.spec, &tunnel.spec)
+            }) {
+                report.error(
+                    format!("forwarding_list[{index}]"),
+                    format!(
+                        "Tunnel '{}'{} binds {}, already bound by '{}'{}",
+                        tunnel.name,
+                        in_origin(*index, *other_index),
+                        tunnel.spec.bind_endpoint(),
+                        other.name,
+                        in_origin(*other_index, *index)
+                    ),
+                );
+                continue;
+            }
+            bound.push((*index, tunnel, side));
+        }
+    }
+
+    /// Substitute `${VAR}` and `${VAR:-default}` from the environment in remote hosts,
+    /// users and key paths and in forwarding entries, and expand a leading `~` in key paths
+    pub fn expand_env(&mut self) -> StunResult<()> {
+        self.expand_with(&|name| std::env::var(name).ok())
+    }
+
+    fn expand_with(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> StunResult<()> {
+        self.remote.expand("remote", lookup)?;
+        for (name, remote) in &mut self.remotes {
+            remote.expand(&format!("remotes.{name}"), lookup)?;
+        }
+
+        expand_entries(&mut self.forwarding_list, "forwarding_list", lookup)
+    }
+
+    /// Deserialize configuration text without validating it or resolving `include`
+    pub fn parse(content: &str, format: ConfigFormat) -> StunResult<Self> {
+        deserialize(content, format).map_err(|issue| StunError::Config(issue.to_string()))
+    }
+
+    /// Serialize configuration text in the given format
+    pub fn serialize(&self, format: ConfigFormat) -> StunResult<String> {
+        let content = match format {
+            ConfigFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::to_string_pretty(self).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml_ng::to_string(self).map_err(|e| e.to_string()),
+        };
+        content.map_err(|e| StunError::Config(format!("Failed to serialize config: {e}")))
+    }
+
+    /// Build a configuration from the ssh_config `Host` blocks that apply to `host`,
+    /// mapping HostName, User, Port, IdentityFile, ProxyJump and the forward directives
+    pub fn from_ssh_config<P: AsRef<Path>>(path: P, host: &str) -> StunResult<Self> {
+        crate::ssh_config::load(path.as_ref(), host)
     }
 
-    /// Save configuration to a JSON file
+    /// Save configuration as JSON, TOML or YAML, chosen by the file's extension
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> StunResult<()> {
-        let content = serde_json::to_string_pretty(self)
-            .map_err(|e| StunError::Config(format!("Failed to serialize config: {e}")))?;
+        let format = ConfigFormat::from_path(path.as_ref());
+        self.to_file_with_format(path, format)
+    }
+
+    /// Save configuration to a file in the given format, whatever its extension
+    pub fn to_file_with_format<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: ConfigFormat,
+    ) -> StunResult<()> {
+        let content = self.serialize(format)?;
 
         std::fs::write(path, content)
             .map_err(|e| StunError::Config(format!("Failed to write config file: {e}")))?;
@@ -84,108 +905,603 @@ impl Config {
         Ok(())
     }
 
-    /// Validate the configuration
+    /// Validate the configuration, failing with every problem found
     pub fn validate(&self) -> StunResult<()> {
-        if self.remote.host.is_empty() {
-            return Err(StunError::Config("Remote host cannot be empty".to_string()));
-        }
+        self.validation_report().into_result()
+    }
+
+    /// Check the configuration and collect every problem, each with its path in the
+    /// document. Only the configuration itself is checked; see
+    /// [`environment_report`](Self::environment_report) for the local system.
+    pub fn validation_report(&self) -> ValidationReport {
+        self.report_with_origins(&[])
+    }
+
+    /// [`validation_report`](Self::validation_report) naming the file each entry
+    /// of `forwarding_list` came from, where known
+    fn report_with_origins(&self, origins: &[String]) -> ValidationReport {
+        let mut report = ValidationReport::default();
 
-        if self.remote.user.is_empty() {
-            return Err(StunError::Config("Remote user cannot be empty".to_string()));
+        if self.remotes.contains_key(DEFAULT_REMOTE) {
+            report.error(
+                format!("remotes.{DEFAULT_REMOTE}"),
+                format!("Remote name '{DEFAULT_REMOTE}' is reserved for the top-level remote"),
+            );
+        }
+        for (name, remote) in &self.remotes {
+            if name.is_empty() {
+                report.error("remotes", "Remote name cannot be empty");
+            }
+            remote.check(name, &format!("remotes.{name}"), &mut report);
         }
 
         if self.forwarding_list.is_empty() {
-            return Err(StunError::Config(
-                "Forwarding list cannot be empty".to_string(),
-            ));
+            report.error("forwarding_list", "Forwarding list cannot be empty");
         }
 
-        // Validate forwarding specifications
-        for spec in &self.forwarding_list {
-            self.validate_forwarding_spec(spec)?;
+        // Entries that fail to parse are left out of the checks across tunnels.
+        // Their name and spec are kept, since a probe key may still refer to them.
+        let mut tunnels = Vec::new();
+        let mut unparsed = Vec::new();
+        for (index, entry) in self.forwarding_list.iter().enumerate() {
+            let message = match self.resolve_entry(entry) {
+                Ok(resolved) => {
+                    tunnels.extend(resolved.into_iter().map(|tunnel| (index, tunnel)));
+                    continue;
+                }
+                Err(StunError::Config(message)) => message,
+                Err(e) => e.to_string(),
+            };
+            report.error(format!("forwarding_list[{index}]"), message);
+            if let ForwardingEntry::Tunnel(TunnelEntry {
+                name: Some(name), ..
+            }) = entry
+            {
+                unparsed.push(name.clone());
+            }
+            unparsed.push(entry_spec_string(entry));
         }
 
-        // Validate remote probes if provided
-        if let Some(map) = &self.remote_probes {
-            // Ensure keys refer to a configured spec
-            let known: std::collections::HashSet<&String> = self.forwarding_list.iter().collect();
-            for (spec_key, target) in map {
-                if !known.contains(spec_key) {
-                    return Err(StunError::Config(format!(
-                        "remote_probes key '{spec_key}' does not match any forwarding_list entry"
-                    )));
+        // The top-level remote is only required when some tunnel uses it
+        if self.remotes.is_empty() || tunnels.iter().any(|(_, t)| t.remote == DEFAULT_REMOTE) {
+            self.remote.check(DEFAULT_REMOTE, "remote", &mut report);
+        }
+        for (index, tunnel) in &tunnels {
+            match self.remote_config(&tunnel.remote) {
+                None => report.error(
+                    format!("forwarding_list[{index}].remote"),
+                    format!(
+                        "Tunnel '{}' refers to unknown remote '{}'",
+                        tunnel.name, tunnel.remote
+                    ),
+                ),
+                Some(remote)
+                    if remote.transport == TransportKind::Native
+                        && tunnel.spec.uses_unix_socket() =>
+                {
+                    report.error(
+                        format!("forwarding_list[{index}]"),
+                        format!(
+                            "Tunnel '{}' forwards a Unix socket, which requires the process transport",
+                            tunnel.name
+                        ),
+                    )
                 }
-                // Validate host:port format for target
-                let mut parts = target.rsplitn(2, ':');
-                let port_str = parts.next().unwrap_or("");
-                let host = parts.next().unwrap_or("");
-                if host.is_empty() || port_str.is_empty() {
-                    return Err(StunError::Config(format!(
-                        "Invalid remote_probe target '{target}', expected host:port"
-                    )));
+                Some(_) => {}
+            }
+        }
+
+        // Names are tunnel keys, so they must be unique
+        let mut names = HashSet::new();
+        for (index, tunnel) in &tunnels {
+            if tunnel.name.is_empty() {
+                report.error(
+                    format!("forwarding_list[{index}].name"),
+                    "Tunnel name cannot be empty",
+                );
+            } else if !names.insert(tunnel.name.as_str()) {
+                report.error(
+                    format!("forwarding_list[{index}]"),
+                    format!("Duplicate tunnel name '{}'", tunnel.name),
+                );
+            }
+        }
+        self.check_duplicate_binds(&tunnels, origins, &mut report);
+
+        // Ensure remote probe keys refer to a configured tunnel
+        if let Some(map) = &self.remote_probes {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            for name in keys {
+                // Keys that may belong to an unparsable entry, including the `name:port`
+                // of a named range, are not reported twice
+                let unparsed = unparsed.iter().any(|entry| {
+                    name == entry
+                        || name
+                            .strip_prefix(entry.as_str())
+                            .is_some_and(|rest| rest.starts_with(':'))
+                });
+                if !names.contains(name.as_str()) && !unparsed {
+                    report.error(
+                        format!("remote_probes.{name}"),
+                        format!("remote_probes key '{name}' does not match any tunnel name"),
+                    );
                 }
-                port_str.parse::<u16>().map_err(|_| {
-                    StunError::Config(format!(
-                        "Invalid port '{port_str}' in remote_probe target '{target}'"
-                    ))
-                })?;
             }
         }
 
         // Validate backoff settings if provided
-        if let Some(base) = self.backoff_base_secs
-            && base == 0
-        {
-            return Err(StunError::Config(
-                "backoff_base_secs must be >= 1".to_string(),
-            ));
+        if self.backoff_base_secs == Some(0) {
+            report.error("backoff_base_secs", "backoff_base_secs must be >= 1");
         }
-        if let Some(max) = self.backoff_max_secs
-            && max == 0
-        {
-            return Err(StunError::Config(
-                "backoff_max_secs must be >= 1".to_string(),
-            ));
+        if self.backoff_max_secs == Some(0) {
+            report.error("backoff_max_secs", "backoff_max_secs must be >= 1");
         }
         if let (Some(base), Some(max)) = (self.backoff_base_secs, self.backoff_max_secs)
             && max < base
         {
-            return Err(StunError::Config(
-                "backoff_max_secs must be >= backoff_base_secs".to_string(),
-            ));
+            report.error(
+                "backoff_max_secs",
+                "backoff_max_secs must be >= backoff_base_secs",
+            );
         }
 
-        Ok(())
+        report
+    }
+
+    /// Check what the configuration needs from the local system: readable key
+    /// files and permission to bind privileged ports
+    pub fn environment_report(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        let mut remotes: Vec<(String, &RemoteConfig)> = Vec::new();
+        if !self.remote.is_unset() {
+            remotes.push(("remote".to_string(), &self.remote));
+        }
+        for (name, remote) in &self.remotes {
+            remotes.push((format!("remotes.{name}"), remote));
+        }
+        for (path, remote) in remotes {
+            let keys =
+                std::iter::once((format!("{path}.key"), &remote.key)).chain(
+                    remote.jump_hosts.iter().enumerate().map(|(index, jump)| {
+                        (format!("{path}.jump_hosts[{index}].key"), &jump.key)
+                    }),
+                );
+            for (key_path, key) in keys {
+                if let Some(key) = key
+                    && let Err(e) = std::fs::File::open(key)
+                {
+                    report.error(key_path, format!("Cannot read key file '{key}': {e}"));
+                }
+            }
+        }
+
+        let unprivileged_start = unprivileged_port_start();
+        let tunnels = self
+            .forwarding_list
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| Some((index, self.resolve_entry(entry).ok()?)))
+            .flat_map(|(index, resolved)| resolved.into_iter().map(move |tunnel| (index, tunnel)));
+        for (index, tunnel) in tunnels {
+            let port = tunnel.spec.bind_port;
+            if port == 0 || port >= unprivileged_start {
+                continue;
+            }
+            let path = format!("forwarding_list[{index}]");
+            match tunnel.spec.resolve_mode(self.mode) {
+                Ok(ForwardingMode::Remote) => {
+                    let user = self.remote_config(&tunnel.remote).map(|r| r.user.as_str());
+                    if user != Some("root") {
+                        report.warning(
+                            path,
+                            format!(
+                                "Binding port {port} on the SSH server usually requires logging in as root"
+                            ),
+                        );
+                    }
+                }
+                Ok(_) if !is_root() => report.error(
+                    path,
+                    format!("Binding port {port} requires root privileges on this host"),
+                ),
+                _ => {}
+            }
+        }
+
+        report
     }
 
-    /// Validate a single forwarding specification
-    fn validate_forwarding_spec(&self, spec: &str) -> StunResult<()> {
-        let parts: Vec<&str> = spec.split(':').collect();
+    /// Names of the configured remotes; the top-level `remote` is listed as
+    /// [`DEFAULT_REMOTE`] unless it was left out
+    pub fn remote_names(&self) -> Vec<String> {
+        let default = (!self.remote.is_unset()).then(|| DEFAULT_REMOTE.to_string());
+        default
+            .into_iter()
+            .chain(self.remotes.keys().cloned())
+            .collect()
+    }
 
-        if parts.len() != 3 && parts.len() != 4 {
+    /// Look up a remote by name, [`DEFAULT_REMOTE`] being the top-level `remote`
+    pub fn remote_config(&self, name: &str) -> Option<&RemoteConfig> {
+        if name == DEFAULT_REMOTE {
+            (!self.remote.is_unset()).then_some(&self.remote)
+        } else {
+            self.remotes.get(name)
+        }
+    }
+
+    /// Single-remote view of this configuration used to build the named remote's client
+    pub fn for_remote(&self, name: &str) -> StunResult<Config> {
+        let remote = self
+            .remote_config(name)
+            .ok_or_else(|| StunError::Config(format!("Unknown remote '{name}'")))?;
+        Ok(Config {
+            remote: remote.clone(),
+            remotes: BTreeMap::new(),
+            ..self.clone()
+        })
+    }
+
+    /// Resolve every forwarding entry into its name, spec and per-tunnel options.
+    /// Port range entries expand into one tunnel per port.
+    pub fn tunnels(&self) -> StunResult<Vec<TunnelConfig>> {
+        let mut tunnels = Vec::new();
+        for entry in &self.forwarding_list {
+            tunnels.extend(self.resolve_entry(entry)?);
+        }
+        Ok(tunnels)
+    }
+
+    fn resolve_entry(&self, entry: &ForwardingEntry) -> StunResult<Vec<TunnelConfig>> {
+        let spec_string = entry_spec_string(entry);
+        let Some(expanded) = ForwardingSpec::expand_range(&spec_string)? else {
+            let spec = ForwardingSpec::parse(&spec_string)?;
+            return Ok(vec![self.resolve_spec(entry, spec, None)?]);
+        };
+
+        // Expanded tunnels are named per port, `name:port` for named entries, and
+        // grouped under the entry's name
+        let group = match entry {
+            ForwardingEntry::Tunnel(TunnelEntry {
+                name: Some(name), ..
+            }) => name.clone(),
+            _ => spec_string,
+        };
+        expanded
+            .iter()
+            .map(|spec| {
+                let spec = ForwardingSpec::parse(spec)?;
+                self.resolve_spec(entry, spec, Some(group.clone()))
+            })
+            .collect()
+    }
+
+    fn resolve_spec(
+        &self,
+        entry: &ForwardingEntry,
+        spec: ForwardingSpec,
+        group: Option<String>,
+    ) -> StunResult<TunnelConfig> {
+        spec.resolve_mode(self.mode)?;
+
+        let mut tunnel = match entry {
+            ForwardingEntry::Spec(_) => TunnelConfig::from_spec(spec),
+            ForwardingEntry::Tunnel(entry) => TunnelConfig {
+                name: match (&entry.name, &group) {
+                    (Some(name), Some(_)) => format!("{name}:{}", spec.bind_port),
+                    (Some(name), None) => name.clone(),
+                    (None, _) => spec.to_spec_string(),
+                },
+                spec,
+                remote: entry
+                    .remote
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_REMOTE.to_string()),
+                probe: entry.probe.as_deref().map(parse_host_port).transpose()?,
+                timeout: entry.timeout,
+                enabled: entry.enabled,
+                tags: entry.tags.clone(),
+                group: None,
+            },
+        };
+        tunnel.group = group;
+
+        if tunnel.probe.is_none()
+            && let Some(target) = self
+                .remote_probes
+                .as_ref()
+                .and_then(|map| map.get(&tunnel.name))
+        {
+            tunnel.probe = Some(parse_host_port(target)?);
+        }
+        Ok(tunnel)
+    }
+}
+
+/// Spec string of an entry; object entries are assembled from `mode`, `bind` and `target`
+fn entry_spec_string(entry: &ForwardingEntry) -> String {
+    match entry {
+        ForwardingEntry::Spec(spec) => spec.clone(),
+        ForwardingEntry::Tunnel(entry) => {
+            let prefix = entry.mode.map_or("", |mode| mode.spec_prefix());
+            match &entry.target {
+                Some(target) => format!("{prefix}{}:{target}", entry.bind),
+                None => format!("{prefix}{}", entry.bind),
+            }
+        }
+    }
+}
+
+/// Deserialize `content`, locating syntax and type errors by line and column
+fn deserialize<T: DeserializeOwned>(
+    content: &str,
+    format: ConfigFormat,
+) -> Result<T, ValidationIssue> {
+    let failed = |message: &str| {
+        ValidationIssue::error(
+            "",
+            format!("Failed to parse config: {}", strip_location(message)),
+        )
+    };
+    match format {
+        ConfigFormat::Json => serde_json::from_str(content).map_err(|e| {
+            let issue = failed(&e.to_string());
+            if e.line() > 0 {
+                issue.at(e.line(), e.column())
+            } else {
+                issue
+            }
+        }),
+        ConfigFormat::Toml => toml::from_str(content).map_err(|e| {
+            let issue = failed(e.message());
+            match e.span() {
+                Some(span) => {
+                    let before = &content[..span.start];
+                    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                    issue.at(
+                        before.matches('\n').count() + 1,
+                        before[line_start..].chars().count() + 1,
+                    )
+                }
+                None => issue,
+            }
+        }),
+        ConfigFormat::Yaml => serde_yaml_ng::from_str(content).map_err(|e| {
+            let issue = failed(&e.to_string());
+            match e.location() {
+                Some(location) => issue.at(location.line(), location.column()),
+                None => issue,
+            }
+        }),
+    }
+}
+
+/// Remove the parser's own `at line L column C` notes; the location is reported separately
+fn strip_location(message: &str) -> String {
+    let mut stripped = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find(" at line ") {
+        stripped.push_str(&rest[..start]);
+        let tail = &rest[start + " at line ".len()..];
+        let tail = tail.trim_start_matches(|c: char| c.is_ascii_digit());
+        let tail = tail.strip_prefix(" column ").unwrap_or(tail);
+        rest = tail.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Lowest port unprivileged processes may bind, per `ip_unprivileged_port_start` on Linux
+fn unprivileged_port_start() -> u16 {
+    std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(1024)
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    current_uid() == 0
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+/// Whether two forwards listen on the same socket path, or the same port of a common address
+fn binds_overlap(a: &ForwardingSpec, b: &ForwardingSpec) -> bool {
+    if a.bind_socket.is_some() || b.bind_socket.is_some() {
+        return a.bind_socket == b.bind_socket;
+    }
+    // Automatic ports are picked from the free ones at launch
+    if a.is_auto_port() || b.is_auto_port() {
+        return false;
+    }
+    fn normalize(address: &str) -> &str {
+        match address {
+            "localhost" => "127.0.0.1",
+            "*" | "::" => "0.0.0.0",
+            other => other,
+        }
+    }
+    let (a_address, b_address) = (
+        normalize(a.effective_bind_address()),
+        normalize(b.effective_bind_address()),
+    );
+    a.bind_port == b.bind_port
+        && (a_address == b_address || a_address == "0.0.0.0" || b_address == "0.0.0.0")
+}
+
+/// Expand variables in the spec strings, binds and targets of `entries`
+fn expand_entries(
+    entries: &mut [ForwardingEntry],
+    field: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> StunResult<()> {
+    for (index, entry) in entries.iter_mut().enumerate() {
+        let field = format!("{field}[{index}]");
+        match entry {
+            ForwardingEntry::Spec(spec) => *spec = expand_spec(spec, &field, lookup)?,
+            ForwardingEntry::Tunnel(entry) => {
+                entry.bind = expand_path(&entry.bind, &format!("{field}.bind"), lookup)?;
+                if let Some(target) = &mut entry.target {
+                    *target = expand_path(target, &format!("{field}.target"), lookup)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Substitute `${VAR}` and `${VAR:-default}` references; `field` names the value in errors.
+/// As in the shell, a set but empty variable expands to nothing, unless a default is
+/// given, which then applies as it does to an unset one.
+fn expand_value(
+    value: &str,
+    field: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> StunResult<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let (reference, tail) = rest[start + 2..].split_once('}').ok_or_else(|| {
+            StunError::Config(format!(
+                "Unterminated variable reference in {field}: '{value}'"
+            ))
+        })?;
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
             return Err(StunError::Config(format!(
-                "Invalid forwarding specification '{spec}'. Expected format: [bind_addr:]port:host:port"
+                "Invalid variable name '{name}' in {field}"
             )));
         }
 
-        // Parse and validate ports
-        let port_indices = if parts.len() == 3 {
-            vec![0, 2]
-        } else {
-            vec![1, 3]
+        let resolved = match (lookup(name), default) {
+            (Some(value), Some(default)) if value.is_empty() => default.to_string(),
+            (Some(value), _) => value,
+            (None, Some(default)) => default.to_string(),
+            (None, None) => {
+                return Err(StunError::Config(format!(
+                    "Environment variable '{name}' used in {field} is not set"
+                )));
+            }
         };
+        expanded.push_str(&resolved);
+        rest = tail;
+    }
 
-        for &idx in &port_indices {
-            parts[idx].parse::<u16>().map_err(|_| {
-                StunError::Config(format!(
-                    "Invalid port '{}' in forwarding specification '{}'",
-                    parts[idx], spec
-                ))
-            })?;
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// [`expand_value`] for a file path, also replacing a leading `~` with `$HOME`
+fn expand_path(
+    path: &str,
+    field: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> StunResult<String> {
+    let path = expand_value(path, field, lookup)?;
+    replace_tilde(&path, lookup)
+        .ok_or_else(|| StunError::Config(format!("Cannot expand '~' in {field}: HOME is not set")))
+}
+
+/// [`expand_value`] for a spec string, also replacing `~` at the start of a Unix
+/// socket path on either side
+fn expand_spec(
+    spec: &str,
+    field: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> StunResult<String> {
+    let spec = expand_value(spec, field, lookup)?;
+    let (_, unprefixed) = ForwardingSpec::split_mode_prefix(&spec);
+    let prefix = &spec[..spec.len() - unprefixed.len()];
+    // `~` appears in no host or port, so a field starting with it is a path
+    let (bind, target) = match unprefixed.find(":~") {
+        Some(at) => (&unprefixed[..at], Some(&unprefixed[at + 1..])),
+        None => (unprefixed, None),
+    };
+
+    let home_missing =
+        || StunError::Config(format!("Cannot expand '~' in {field}: HOME is not set"));
+    let mut expanded = prefix.to_string();
+    expanded.push_str(&replace_tilde(bind, lookup).ok_or_else(home_missing)?);
+    if let Some(target) = target {
+        expanded.push(':');
+        expanded.push_str(&replace_tilde(target, lookup).ok_or_else(home_missing)?);
+    }
+    Ok(expanded)
+}
+
+/// Replace a leading `~` in `path` with `HOME`; `None` when that is needed but unset
+fn replace_tilde(path: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            Some(format!("{}{rest}", lookup("HOME")?))
         }
+        _ => Some(path.to_string()),
+    }
+}
 
-        Ok(())
+/// Replace a leading `~` in `path` with the home directory, keeping it when `HOME` is unset
+pub(crate) fn expand_home(path: &str) -> String {
+    replace_tilde(path, &|name| std::env::var(name).ok()).unwrap_or_else(|| path.to_string())
+}
+
+/// The current user's home directory, from `HOME`
+pub(crate) fn home_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+/// Per-user directory under the temp directory, `stun-$UID` on Unix, for sockets and
+/// files other users must not be able to replace
+pub(crate) fn private_dir_path() -> PathBuf {
+    #[cfg(unix)]
+    let name = format!("stun-{}", current_uid());
+    #[cfg(not(unix))]
+    let name = "stun".to_string();
+    std::env::temp_dir().join(name)
+}
+
+/// Create `dir` readable only by its owner, or check that an existing one is ours and private
+#[cfg(unix)]
+pub(crate) fn ensure_private_dir(dir: &Path) -> StunResult<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e.into()),
+        Err(_) => {}
+    }
+    let metadata = std::fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o077 != 0 {
+        return Err(StunError::Config(format!(
+            "Directory {} must be a directory only accessible by the current user",
+            dir.display()
+        )));
     }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn ensure_private_dir(dir: &Path) -> StunResult<()> {
+    std::fs::create_dir_all(dir)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn current_uid() -> libc::uid_t {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() }
 }
 
 #[cfg(test)]
@@ -198,20 +1514,571 @@ mod tests {
             mode: ForwardingMode::Local,
             remote: RemoteConfig {
                 host: "example.com".to_string(),
-                port: 22,
                 user: "testuser".to_string(),
-                key: None,
+                ..Default::default()
             },
-            forwarding_list: vec!["8080:127.0.0.1:8080".to_string()],
+            forwarding_list: vec!["8080:127.0.0.1:8080".into()],
             timeout: Some(5),
-            remote_probes: None,
-            backoff_base_secs: None,
-            backoff_max_secs: None,
+            ..Default::default()
         };
 
         assert!(config.validate().is_ok());
     }
 
+    #[test]
+    fn test_dynamic_mode_validation() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remote": {"host": "example.com", "user": "testuser"},
+                "forwarding_list": ["127.0.0.1:1080"]
+            }"#,
+        )
+        .unwrap();
+
+        // Dynamic specs only fit dynamic mode, and vice versa
+        assert!(config.validate().is_err());
+        config.mode = ForwardingMode::Dynamic;
+        assert!(config.validate().is_ok());
+        config.forwarding_list = vec!["8080:127.0.0.1:8080".into()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_mode_prefix_validation() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remote": {"host": "example.com", "user": "testuser"},
+                "forwarding_list": ["8080:127.0.0.1:8080", "R:9000:127.0.0.1:9000", "D:1080"]
+            }"#,
+        )
+        .unwrap();
+
+        // Prefixed entries override the default mode, so directions can mix
+        assert!(config.validate().is_ok());
+        config.mode = ForwardingMode::Dynamic;
+        assert!(config.validate().is_err());
+        config.forwarding_list.remove(0);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_unix_socket_validation() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remote": {"host": "example.com", "user": "testuser"},
+                "forwarding_list": [
+                    "/tmp/docker.sock:/var/run/docker.sock",
+                    "5432:/run/postgresql/.s.PGSQL.5432"
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        // Unix socket paths on either side, each bound at most once
+        assert!(config.validate().is_ok());
+        config
+            .forwarding_list
+            .push("/tmp/docker.sock:/var/run/other.sock".into());
+        assert!(config.validate().is_err());
+
+        // The native transport cannot forward sockets
+        config.forwarding_list.pop();
+        config.remote.transport = TransportKind::Native;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_auto_port_validation() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remote": {"host": "example.com", "user": "testuser"},
+                "forwarding_list": ["auto:127.0.0.1:8080", "auto:127.0.0.1:8081"]
+            }"#,
+        )
+        .unwrap();
+
+        // Automatic bind ports never clash, but only work where stun binds the port
+        assert!(config.validate().is_ok());
+        config.forwarding_list.push("R:auto:127.0.0.1:9000".into());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_object_entries() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remote": {"host": "example.com", "user": "testuser"},
+                "forwarding_list": [
+                    "8080:127.0.0.1:80",
+                    {"name": "db", "bind": "5432", "target": "db.internal:5432",
+                     "probe": "db.internal:5432", "timeout": 3, "tags": ["prod"]},
+                    {"name": "socks", "bind": "1080", "mode": "dynamic", "enabled": false}
+                ],
+                "timeout": 5,
+                "remote_probes": {"8080:127.0.0.1:80": "127.0.0.1:80"}
+            }"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let tunnels = config.tunnels().unwrap();
+        let names: Vec<&str> = tunnels.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["8080:127.0.0.1:80", "db", "socks"]);
+        assert_eq!(tunnels[0].probe, Some(("127.0.0.1".to_string(), 80)));
+        assert_eq!(tunnels[1].probe, Some(("db.internal".to_string(), 5432)));
+        assert_eq!(tunnels[1].timeout, Some(3));
+        assert_eq!(tunnels[1].tags, ["prod"]);
+        assert_eq!(tunnels[2].spec.mode, Some(ForwardingMode::Dynamic));
+        assert!(!tunnels[2].enabled);
+
+        // remote_probes keys are tunnel names
+        config.remote_probes = Some(HashMap::from([(
+            "5432:db.internal:5432".to_string(),
+            "db.internal:5432".to_string(),
+        )]));
+        assert!(config.validate().is_err());
+
+        config.remote_probes = None;
+        config
+            .forwarding_list
+            .push(ForwardingEntry::Tunnel(TunnelEntry {
+                name: Some("db".to_string()),
+                bind: "5433".to_string(),
+                target: Some("db.internal:5432".to_string()),
+                mode: None,
+                remote: None,
+                probe: None,
+                timeout: None,
+                enabled: true,
+                tags: Vec::new(),
+            }));
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_port_ranges() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remote": {"host": "example.com", "user": "testuser"},
+                "forwarding_list": [
+                    "8910:127.0.0.1:8910",
+                    "9900-9902:127.0.0.1:9900-9902",
+                    {"name": "web", "bind": "8000-8001", "target": "web.internal:80-81"}
+                ],
+                "remote_probes": {"9901:127.0.0.1:9901": "127.0.0.1:9901"}
+            }"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let tunnels = config.tunnels().unwrap();
+        let names: Vec<&str> = tunnels.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "8910:127.0.0.1:8910",
+                "9900:127.0.0.1:9900",
+                "9901:127.0.0.1:9901",
+                "9902:127.0.0.1:9902",
+                "web:8000",
+                "web:8001"
+            ]
+        );
+        assert_eq!(tunnels[0].group, None);
+        assert_eq!(
+            tunnels[2].group.as_deref(),
+            Some("9900-9902:127.0.0.1:9900-9902")
+        );
+        assert_eq!(tunnels[2].probe, Some(("127.0.0.1".to_string(), 9901)));
+        assert_eq!(tunnels[5].group.as_deref(), Some("web"));
+        assert_eq!(tunnels[5].spec.remote_port, 81);
+
+        // Ranges of different lengths and ports claimed twice are reported
+        config.forwarding_list[1] = "9900-9910:127.0.0.1:9900-9905".into();
+        let report = config.validation_report();
+        assert_eq!(report.errors().next().unwrap().path, "forwarding_list[1]");
+        config.forwarding_list[1] = "8905-8915:127.0.0.1:9900-9910".into();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_named_remotes() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remotes": {
+                    "staging": {"host": "bastion.staging", "user": "deploy"},
+                    "prod": {"host": "bastion.prod", "user": "deploy", "port": 2222}
+                },
+                "forwarding_list": [
+                    {"name": "staging-db", "bind": "5432", "target": "db:5432", "remote": "staging"},
+                    {"name": "prod-db", "bind": "5433", "target": "db:5432", "remote": "prod"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(config.remote.is_unset());
+        assert!(config.validate().is_ok());
+        assert_eq!(config.remote_names(), ["prod", "staging"]);
+
+        let mut staging = config.remotes["staging"].clone();
+        staging.fallbacks = vec![Endpoint {
+            host: "::1".to_string(),
+            port: 2222,
+        }];
+        let endpoints: Vec<String> = staging
+            .endpoints()
+            .iter()
+            .map(Endpoint::to_string)
+            .collect();
+        assert_eq!(endpoints, ["bastion.staging:22", "[::1]:2222"]);
+        assert_eq!(staging.at_endpoint(&staging.fallbacks[0]).host, "::1");
+
+        let prod = config.for_remote("prod").unwrap();
+        assert_eq!(prod.remote.host, "bastion.prod");
+        assert_eq!(prod.remote.port, 2222);
+        assert!(prod.remotes.is_empty());
+
+        // Spec strings use the top-level remote, which this config leaves out
+        config.forwarding_list.push("8080:127.0.0.1:80".into());
+        assert!(config.validate().is_err());
+        config.remote = config.remotes["staging"].clone();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.remote_names(), [DEFAULT_REMOTE, "prod", "staging"]);
+
+        config.remotes.remove("prod");
+        assert!(config.validate().is_err());
+        config
+            .remotes
+            .insert(DEFAULT_REMOTE.to_string(), config.remote.clone());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_toml_and_yaml_formats() {
+        let toml = r#"
+            mode = "local"
+            forwarding_list = [
+                "8080:127.0.0.1:80",
+                # Reporting database, read-only replica
+                { name = "db", bind = "5432", target = "db.internal:5432", tags = ["prod"] },
+            ]
+
+            [remote]
+            host = "example.com"
+            user = "testuser"
+            host_key_policy = { pinned = ["SHA256:nThbg6kXUpJWGl7E1IGOCspRomTxdCARLviKw6E5SY8"] }
+        "#;
+        let config = Config::parse(toml, ConfigFormat::Toml).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.tunnels().unwrap()[1].name, "db");
+
+        let yaml = config.serialize(ConfigFormat::Yaml).unwrap();
+        assert_eq!(Config::parse(&yaml, ConfigFormat::Yaml).unwrap(), config);
+
+        // The extension picks the format for both writing and reading
+        for name in ["test_config.toml", "test_config.yml"] {
+            let path = std::env::temp_dir().join(name);
+            config.to_file(&path).unwrap();
+            let content = std::fs::read_to_string(&path).unwrap();
+            let loaded = Config::from_file(&path).unwrap();
+            let _ = std::fs::remove_file(&path);
+
+            assert!(!content.trim_start().starts_with('{'));
+            assert_eq!(loaded, config);
+        }
+
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("stun.YAML")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("stun.conf")),
+            ConfigFormat::Json
+        );
+        assert_eq!("yml".parse::<ConfigFormat>().unwrap(), ConfigFormat::Yaml);
+        assert!("ini".parse::<ConfigFormat>().is_err());
+    }
+
+    #[test]
+    fn test_env_expansion() {
+        let mut config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remote": {
+                    "host": "${STUN_TEST_HOST}",
+                    "user": "${STUN_TEST_USER:-deploy}",
+                    "key": "~/.ssh/bastion_key",
+                    "jump_hosts": [{"host": "gw.${STUN_TEST_DOMAIN}", "key": "${HOME}/.ssh/gw"}]
+                },
+                "forwarding_list": [
+                    "${STUN_TEST_PORT:-8080}:127.0.0.1:80",
+                    {"bind": "5432", "target": "db.${STUN_TEST_DOMAIN}:5432"},
+                    "~/docker.sock:/var/run/docker.sock",
+                    "R:2375:~/run/docker.sock",
+                    {"bind": "~/pg.sock", "target": "127.0.0.1:5432"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let unexpanded = config.clone();
+
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/deploy".to_string()),
+            "STUN_TEST_HOST" => Some("bastion.example.com".to_string()),
+            "STUN_TEST_DOMAIN" => Some("example.com".to_string()),
+            "STUN_TEST_USER" => Some(String::new()),
+            _ => None,
+        };
+        config.expand_with(&lookup).unwrap();
+        assert!(config.validate().is_ok());
+
+        assert_eq!(config.remote.host, "bastion.example.com");
+        assert_eq!(config.remote.user, "deploy");
+        assert_eq!(
+            config.remote.key.as_deref(),
+            Some("/home/deploy/.ssh/bastion_key")
+        );
+        assert_eq!(config.remote.jump_hosts[0].host, "gw.example.com");
+        assert_eq!(
+            config.remote.jump_hosts[0].key.as_deref(),
+            Some("/home/deploy/.ssh/gw")
+        );
+        let tunnels = config.tunnels().unwrap();
+        assert_eq!(tunnels[0].name, "8080:127.0.0.1:80");
+        assert_eq!(tunnels[1].name, "5432:db.example.com:5432");
+        assert_eq!(
+            tunnels[2].spec.bind_socket.as_deref(),
+            Some("/home/deploy/docker.sock")
+        );
+        assert_eq!(
+            tunnels[3].spec.remote_socket.as_deref(),
+            Some("/home/deploy/run/docker.sock")
+        );
+        assert_eq!(
+            tunnels[4].spec.bind_socket.as_deref(),
+            Some("/home/deploy/pg.sock")
+        );
+
+        // Undefined variables without a default are reported with their location
+        let mut config = unexpanded;
+        let error = config
+            .expand_with(&|name| (name == "HOME").then(|| "/root".to_string()))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("STUN_TEST_HOST"), "{error}");
+        assert!(error.contains("remote.host"), "{error}");
+
+        let lookup = |_: &str| None;
+        assert!(expand_value("${UNTERMINATED", "host", &lookup).is_err());
+        assert!(expand_value("${}", "host", &lookup).is_err());
+        assert_eq!(expand_value("~user", "host", &lookup).unwrap(), "~user");
+
+        // Set but empty is still set; only a default replaces it
+        let lookup = |_: &str| Some(String::new());
+        assert_eq!(expand_value("a${EMPTY}b", "host", &lookup).unwrap(), "ab");
+        assert_eq!(expand_value("${EMPTY:-x}", "host", &lookup).unwrap(), "x");
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = std::env::temp_dir().join(format!("stun_includes_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        let write = |name: &str, content: &str| std::fs::write(dir.join(name), content).unwrap();
+
+        write(
+            "shared/databases.yaml",
+            "include: [../cache.json]\nforwarding_list:\n  - {name: db, bind: '5432', target: 'db:5432'}\n\
+             remote_probes: {db: 'db:5432'}\n",
+        );
+        write(
+            "cache.json",
+            r#"{"forwarding_list": ["6379:cache:6379", "R:127.0.0.1:5432:127.0.0.1:5432"]}"#,
+        );
+        write(
+            "config.json",
+            r#"{
+                "mode": "local",
+                "remote": {"host": "example.com", "user": "deploy"},
+                "include": ["shared/databases.yaml"],
+                "forwarding_list": ["8080:127.0.0.1:80"]
+            }"#,
+        );
+        write(
+            "conflict.json",
+            r#"{
+                "mode": "local",
+                "remote": {"host": "example.com", "user": "deploy"},
+                "include": ["shared/databases.yaml"],
+                "forwarding_list": ["0.0.0.0:6379:127.0.0.1:6379"]
+            }"#,
+        );
+        write(
+            "cycle.json",
+            r#"{"mode": "local", "include": ["cycle.json"], "forwarding_list": []}"#,
+        );
+
+        let config = Config::from_file(dir.join("config.json"));
+        let conflict = Config::from_file(dir.join("conflict.json"));
+        let cycle = Config::from_file(dir.join("cycle.json"));
+        let fragment = Config::from_file(dir.join("cache.json"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Own entries first, then each fragment before the fragments it includes. The
+        // R: forward binds 5432 on the server, so it does not clash with db.
+        let config = config.unwrap();
+        let names: Vec<String> = config
+            .tunnels()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "8080:127.0.0.1:80",
+                "db",
+                "6379:cache:6379",
+                "R:127.0.0.1:5432:127.0.0.1:5432"
+            ]
+        );
+        assert!(config.include.is_empty());
+        assert_eq!(
+            config.tunnels().unwrap()[1].probe,
+            Some(("db".to_string(), 5432))
+        );
+
+        // A wildcard bind overlaps the fragment's loopback one
+        let error = conflict.unwrap_err().to_string();
+        assert!(error.contains("'6379:cache:6379'"), "{error}");
+        assert!(error.contains("cache.json"), "{error}");
+        assert!(error.contains("conflict.json"), "{error}");
+        assert!(cycle.unwrap_err().to_string().contains("cycle"));
+        // Fragments are not complete configurations on their own
+        assert!(fragment.is_err());
+    }
+
+    #[test]
+    fn test_validation_report() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "mode": "local",
+                "remote": {"host": "", "user": "deploy", "key": "/nonexistent/stun_key"},
+                "forwarding_list": [
+                    "8080:127.0.0.1:80",
+                    "8081:127.0.0.1:99999",
+                    "0.0.0.0:8080:127.0.0.1:81",
+                    {"bind": "9000", "target": "a:1", "remote": "ghost"}
+                ],
+                "remote_probes": {"8081:127.0.0.1:99999": "a:1", "stale": "a:1"},
+                "backoff_base_secs": 0
+            }"#,
+        )
+        .unwrap();
+
+        let report = config.validation_report();
+        let paths: Vec<&str> = report.errors().map(|issue| issue.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "forwarding_list[1]",
+                "remote.host",
+                "forwarding_list[3].remote",
+                "forwarding_list[2]",
+                // The key of the unparsable entry does not hide other stale keys
+                "remote_probes.stale",
+                "backoff_base_secs"
+            ]
+        );
+        let error = config.validate().unwrap_err().to_string();
+        assert!(
+            error.contains("remote.host: Remote host cannot be empty"),
+            "{error}"
+        );
+        assert!(
+            error.contains("already bound by '8080:127.0.0.1:80'"),
+            "{error}"
+        );
+
+        let environment = config.environment_report();
+        assert_eq!(environment.errors().next().unwrap().path, "remote.key");
+
+        // Parse errors carry the line and column
+        let cases = [
+            (
+                "{\n  \"mode\": \"local\",\n  \"timeout\": \"soon\"\n}",
+                ConfigFormat::Json,
+                3,
+            ),
+            ("mode = \"local\"\ntimeout = soon\n", ConfigFormat::Toml, 2),
+            (
+                "mode: local\nforwarding_list:\n  - a\n  b: [\n",
+                ConfigFormat::Yaml,
+                4,
+            ),
+        ];
+        for (content, format, line) in cases {
+            let issue = deserialize::<Config>(content, format).unwrap_err();
+            assert_eq!(issue.line, Some(line), "{format:?}: {issue}");
+            assert!(issue.column.is_some());
+            assert!(!issue.message.contains(" at line "), "{issue}");
+        }
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let path = std::env::temp_dir().join("test_overrides.yaml");
+        std::fs::write(
+            &path,
+            "mode: local\nremote: {host: baked.example.com, user: deploy}\n\
+             forwarding_list: ['8080:127.0.0.1:80']\ntimeout: 5\n",
+        )
+        .unwrap();
+
+        let env = ConfigOverrides::from_vars([
+            ("STUN_HOST", "env.example.com"),
+            ("STUN_PORT", "2222"),
+            ("STUN_FORWARD", "9000:127.0.0.1:9000, 9001:127.0.0.1:9001"),
+            ("STUN_TIMEOUT", "7"),
+            ("HOME", "/root"),
+        ])
+        .unwrap();
+        let cli = ConfigOverrides {
+            host: Some("cli.example.com".to_string()),
+            mode: Some(ForwardingMode::Remote),
+            ..Default::default()
+        };
+        let source = ConfigSource::new(&path).with_overrides(env.merge(cli));
+        let config = source.load();
+        let _ = std::fs::remove_file(&path);
+        let config = config.unwrap();
+
+        // CLI beats the environment, which beats the file
+        assert_eq!(config.remote.host, "cli.example.com");
+        assert_eq!(config.remote.port, 2222);
+        assert_eq!(config.remote.user, "deploy");
+        assert_eq!(config.mode, ForwardingMode::Remote);
+        assert_eq!(config.timeout, Some(7));
+        assert_eq!(
+            config.forwarding_list,
+            vec!["9000:127.0.0.1:9000".into(), "9001:127.0.0.1:9001".into()]
+        );
+
+        assert!(ConfigOverrides::from_vars([("STUN_PORT", "ssh")]).is_err());
+        assert!(ConfigOverrides::from_vars([("STUN_MODE", "sideways")]).is_err());
+        assert!(
+            ConfigOverrides::from_vars([("PATH", "/bin")])
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn test_config_file_operations() {
         let config = Config {
@@ -221,15 +2088,11 @@ mod tests {
                 port: 2222,
                 user: "admin".to_string(),
                 key: Some("/path/to/key".to_string()),
+                ..Default::default()
             },
-            forwarding_list: vec![
-                "8080:127.0.0.1:8080".to_string(),
-                "9000:localhost:9000".to_string(),
-            ],
+            forwarding_list: vec!["8080:127.0.0.1:8080".into(), "9000:localhost:9000".into()],
             timeout: Some(10),
-            remote_probes: None,
-            backoff_base_secs: None,
-            backoff_max_secs: None,
+            ..Default::default()
         };
 
         // Create a temporary file for testing
@@ -0,0 +1,389 @@
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{ConfigSource, ensure_private_dir, private_dir_path},
+    error::{StunError, StunResult},
+    forwarding::ForwardingSpec,
+    manager::{ReloadSummary, TunnelManager},
+    status::TunnelStatus,
+};
+
+/// Request sent to a running daemon over its control socket.
+///
+/// The wire format is one JSON object per line, e.g. `{"command":"restart","tunnel":"8080:127.0.0.1:80"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Full status of every tunnel
+    Status,
+    /// Names of all tunnels
+    List,
+    /// Restart a single tunnel
+    Restart { tunnel: String },
+    /// Add a tunnel from a forwarding spec string
+    Add { spec: String },
+    /// Remove a tunnel
+    Remove { tunnel: String },
+    /// Pause a tunnel
+    Pause { tunnel: String },
+    /// Resume a paused tunnel
+    Resume { tunnel: String },
+    /// Re-read the configuration file the daemon was started with
+    Reload,
+    /// Stop all tunnels and exit the daemon
+    Shutdown,
+}
+
+/// Response to a [`ControlRequest`], also one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum ControlResponse {
+    /// Request succeeded and has no payload
+    Ok,
+    /// Reply to `status`
+    Status { tunnels: Vec<TunnelStatus> },
+    /// Reply to `list`
+    List { tunnels: Vec<String> },
+    /// Reply to `add`
+    Added { tunnel: String },
+    /// Reply to `reload`
+    Reloaded { summary: ReloadSummary },
+    /// Request failed
+    Error { message: String },
+}
+
+/// Default location of the control socket: `$XDG_RUNTIME_DIR/stun.sock`, otherwise
+/// `stun.sock` in a private `stun-$UID` directory under the temp directory
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(private_dir_path)
+        .join("stun.sock")
+}
+
+/// Unix domain socket server exposing a [`TunnelManager`] to `stun ctl`
+pub struct ControlServer {
+    listener: UnixListener,
+    path: PathBuf,
+    manager: Arc<TunnelManager>,
+    config_source: Option<ConfigSource>,
+}
+
+impl ControlServer {
+    /// Bind the control socket at `path`, replacing a stale socket file if present.
+    /// `config_source` is re-read on `reload` requests.
+    pub fn bind<P: AsRef<Path>>(
+        path: P,
+        manager: Arc<TunnelManager>,
+        config_source: Option<ConfigSource>,
+    ) -> StunResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        // The temp directory is shared, so the fallback location gets a private directory
+        let private_dir = private_dir_path();
+        if path.parent() == Some(private_dir.as_path()) {
+            ensure_private_dir(&private_dir)?;
+        }
+
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                return Err(StunError::Config(format!(
+                    "Control socket {} is already in use by another daemon",
+                    path.display()
+                )));
+            }
+            std::fs::remove_file(&path)?;
+        }
+
+        // Only the owning user may control the daemon
+        let listener = UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+
+        info!("Control socket listening on {}", path.display());
+        Ok(Self {
+            listener,
+            path,
+            manager,
+            config_source,
+        })
+    }
+
+    /// Accept control connections until a `shutdown` request arrives.
+    /// The request is acknowledged before this returns; the caller stops the manager.
+    pub async fn run(self) {
+        let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
+
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let manager = Arc::clone(&self.manager);
+                        let config_source = self.config_source.clone();
+                        let shutdown_tx = shutdown_tx.clone();
+                        tokio::spawn(async move {
+                            let result =
+                                handle_connection(stream, manager, config_source, shutdown_tx).await;
+                            if let Err(e) = result {
+                                debug!("Control connection closed with error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => warn!("Failed to accept control connection: {}", e),
+                },
+                _ = shutdown_rx.recv() => {
+                    info!("Shutdown requested over control socket");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Serve line-delimited requests on one connection
+async fn handle_connection(
+    stream: UnixStream,
+    manager: Arc<TunnelManager>,
+    config_source: Option<ConfigSource>,
+    shutdown_tx: mpsc::Sender<()>,
+) -> StunResult<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (response, shutdown) = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => {
+                debug!("Control request: {:?}", request);
+                let shutdown = request == ControlRequest::Shutdown;
+                (
+                    dispatch(request, &manager, config_source.as_ref()).await,
+                    shutdown,
+                )
+            }
+            Err(e) => (
+                ControlResponse::Error {
+                    message: format!("Invalid request: {e}"),
+                },
+                false,
+            ),
+        };
+
+        let mut payload = serde_json::to_string(&response)
+            .map_err(|e| StunError::Other(format!("Failed to encode response: {e}")))?;
+        payload.push('\n');
+        writer.write_all(payload.as_bytes()).await?;
+
+        if shutdown {
+            let _ = shutdown_tx.send(()).await;
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Execute a request against the manager
+async fn dispatch(
+    request: ControlRequest,
+    manager: &TunnelManager,
+    config_source: Option<&ConfigSource>,
+) -> ControlResponse {
+    let result = match request {
+        ControlRequest::Status => Ok(ControlResponse::Status {
+            tunnels: manager.get_status().await,
+        }),
+        ControlRequest::List => Ok(ControlResponse::List {
+            tunnels: manager
+                .get_status()
+                .await
+                .into_iter()
+                .map(|status| status.name)
+                .collect(),
+        }),
+        ControlRequest::Restart { tunnel } => manager
+            .restart_tunnel(&tunnel)
+            .await
+            .map(|_| ControlResponse::Ok),
+        ControlRequest::Add { spec } => match ForwardingSpec::parse(&spec) {
+            Ok(spec) => manager
+                .add_tunnel(spec)
+                .await
+                .map(|tunnel| ControlResponse::Added { tunnel }),
+            Err(e) => Err(e),
+        },
+        ControlRequest::Remove { tunnel } => manager
+            .remove_tunnel(&tunnel)
+            .await
+            .map(|_| ControlResponse::Ok),
+        ControlRequest::Pause { tunnel } => manager
+            .pause_tunnel(&tunnel)
+            .await
+            .map(|_| ControlResponse::Ok),
+        ControlRequest::Resume { tunnel } => manager
+            .resume_tunnel(&tunnel)
+            .await
+            .map(|_| ControlResponse::Ok),
+        ControlRequest::Reload => match config_source {
+            Some(source) => match source.load() {
+                Ok(config) => manager
+                    .reload(config)
+                    .await
+                    .map(|summary| ControlResponse::Reloaded { summary }),
+                Err(e) => Err(e),
+            },
+            None => Err(StunError::Config(
+                "Daemon was started without a configuration file".to_string(),
+            )),
+        },
+        ControlRequest::Shutdown => Ok(ControlResponse::Ok),
+    };
+
+    result.unwrap_or_else(|e| ControlResponse::Error {
+        message: e.to_string(),
+    })
+}
+
+/// Send a single request to the daemon listening at `path` and wait for the reply
+pub async fn send_request<P: AsRef<Path>>(
+    path: P,
+    request: &ControlRequest,
+) -> StunResult<ControlResponse> {
+    let path = path.as_ref();
+    let stream = UnixStream::connect(path).await.map_err(|e| {
+        StunError::Network(format!(
+            "Failed to connect to control socket {}: {e}",
+            path.display()
+        ))
+    })?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut payload = serde_json::to_string(request)
+        .map_err(|e| StunError::Other(format!("Failed to encode request: {e}")))?;
+    payload.push('\n');
+    writer.write_all(payload.as_bytes()).await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| StunError::Network("Daemon closed the connection".to_string()))?;
+
+    serde_json::from_str(&line)
+        .map_err(|e| StunError::Other(format!("Invalid response from daemon: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, ForwardingMode, RemoteConfig};
+
+    #[test]
+    fn test_request_wire_format() {
+        let request = ControlRequest::Restart {
+            tunnel: "8080:127.0.0.1:80".to_string(),
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            json,
+            r#"{"command":"restart","tunnel":"8080:127.0.0.1:80"}"#
+        );
+
+        let decoded: ControlRequest = serde_json::from_str(r#"{"command":"status"}"#).unwrap();
+        assert_eq!(decoded, ControlRequest::Status);
+    }
+
+    #[test]
+    fn test_private_socket_dir() {
+        let dir = std::env::temp_dir().join(format!("stun-test-dir-{}", std::process::id()));
+        ensure_private_dir(&dir).unwrap();
+        let mode = || std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(), 0o700);
+        ensure_private_dir(&dir).unwrap();
+
+        // A directory others can enter is not trusted with the socket
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(ensure_private_dir(&dir).is_err());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_control_round_trip() {
+        let config = Config {
+            mode: ForwardingMode::Local,
+            remote: RemoteConfig {
+                host: "127.0.0.1".to_string(),
+                // Nothing listens on port 1, so ssh fails fast instead of prompting
+                port: 1,
+                user: "testuser".to_string(),
+                ..Default::default()
+            },
+            forwarding_list: vec!["18180:127.0.0.1:8080".into()],
+            timeout: Some(1),
+            ..Default::default()
+        };
+        let mut manager = TunnelManager::new(config).unwrap();
+        let handle = manager.start_background().await.unwrap();
+        let manager = Arc::new(manager);
+
+        let socket = std::env::temp_dir().join(format!("stun-test-{}.sock", std::process::id()));
+        let server = ControlServer::bind(&socket, Arc::clone(&manager), None).unwrap();
+        let server_task = tokio::spawn(server.run());
+        assert_eq!(
+            std::fs::metadata(&socket).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        let response = send_request(&socket, &ControlRequest::List).await.unwrap();
+        assert_eq!(
+            response,
+            ControlResponse::List {
+                tunnels: vec!["18180:127.0.0.1:8080".to_string()]
+            }
+        );
+
+        let response = send_request(
+            &socket,
+            &ControlRequest::Remove {
+                tunnel: "missing".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        assert!(matches!(response, ControlResponse::Error { .. }));
+
+        let response = send_request(&socket, &ControlRequest::Reload)
+            .await
+            .unwrap();
+        assert!(matches!(response, ControlResponse::Error { .. }));
+
+        let response = send_request(&socket, &ControlRequest::Shutdown)
+            .await
+            .unwrap();
+        assert_eq!(response, ControlResponse::Ok);
+        server_task.await.unwrap();
+        assert!(!socket.exists());
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
+}
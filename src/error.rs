@@ -14,6 +14,10 @@ pub enum StunError {
     #[error("SSH error: {0}")]
     Ssh(String),
 
+    /// Host key did not satisfy the configured policy
+    #[error("Host key verification failed: {0}")]
+    HostKey(String),
+
     /// Network connection errors
     #[error("Network error: {0}")]
     Network(String),
@@ -26,6 +30,17 @@ pub enum StunError {
     #[error("Tunnel error: {0}")]
     Tunnel(String),
 
+    /// A local bind port is taken by another process
+    #[error(
+        "Port {address} is already in use{}",
+        .owner.as_ref().map(|owner| format!(" by {owner}")).unwrap_or_default()
+    )]
+    PortInUse {
+        address: String,
+        /// Process holding the port, when it could be determined
+        owner: Option<String>,
+    },
+
     /// Health check errors
     #[error("Health check error: {0}")]
     HealthCheck(String),
@@ -0,0 +1,47 @@
+use std::time::SystemTime;
+
+/// Lifecycle events emitted by the tunnel manager.
+///
+/// Subscribe with [`TunnelManager::subscribe`](crate::TunnelManager::subscribe).
+/// Every variant carries the key of the tunnel it refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TunnelEvent {
+    /// SSH process for the tunnel was launched
+    Started { tunnel: String },
+    /// Tunnel passed a health check after not being healthy
+    Healthy { tunnel: String },
+    /// Health check failed, but not often enough yet to trigger a restart
+    Degraded { tunnel: String, failures: u32 },
+    /// Tunnel is considered down (failure threshold reached or launch failed)
+    Down { tunnel: String },
+    /// A restart attempt is scheduled for the given time
+    RestartScheduled { tunnel: String, at: SystemTime },
+    /// Tunnel was relaunched after a failure or on request
+    Restarted { tunnel: String },
+    /// Relaunching the tunnel failed
+    RestartFailed { tunnel: String, error: String },
+    /// Tunnel cannot start, e.g. because its port is taken, and will not be retried
+    Failed { tunnel: String, error: String },
+    /// Tunnel was paused through the control API
+    Paused { tunnel: String },
+    /// Tunnel was removed through the control API
+    Removed { tunnel: String },
+}
+
+impl TunnelEvent {
+    /// Key of the tunnel this event refers to
+    pub fn tunnel(&self) -> &str {
+        match self {
+            TunnelEvent::Started { tunnel }
+            | TunnelEvent::Healthy { tunnel }
+            | TunnelEvent::Degraded { tunnel, .. }
+            | TunnelEvent::Down { tunnel }
+            | TunnelEvent::RestartScheduled { tunnel, .. }
+            | TunnelEvent::Restarted { tunnel }
+            | TunnelEvent::RestartFailed { tunnel, .. }
+            | TunnelEvent::Failed { tunnel, .. }
+            | TunnelEvent::Paused { tunnel }
+            | TunnelEvent::Removed { tunnel } => tunnel,
+        }
+    }
+}
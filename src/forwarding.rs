@@ -1,16 +1,51 @@
-use crate::error::{StunError, StunResult};
+use std::{borrow::Cow, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ForwardingMode,
+    error::{StunError, StunResult},
+};
+
+/// Bind port written in place of a number to have a free port picked at launch
+pub const AUTO_PORT: &str = "auto";
 
 /// Represents a port forwarding specification
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ForwardingSpec {
     /// Local/bind address (optional)
     pub bind_address: Option<String>,
     /// Local/bind port
     pub bind_port: u16,
-    /// Remote host
+    /// Remote host (empty for dynamic forwarding)
     pub remote_host: String,
-    /// Remote port
+    /// Remote port (0 for dynamic forwarding)
     pub remote_port: u16,
+    /// Direction of this forward; None follows the configured default mode
+    #[serde(default)]
+    pub mode: Option<ForwardingMode>,
+    /// Unix socket path listened on instead of `bind_address`/`bind_port`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_socket: Option<String>,
+    /// Unix socket path forwarded to instead of `remote_host`/`remote_port`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_socket: Option<String>,
+}
+
+/// One side of a forward: a TCP address or a Unix socket path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketEndpoint {
+    Tcp { host: String, port: u16 },
+    Unix(PathBuf),
+}
+
+impl std::fmt::Display for SocketEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SocketEndpoint::Tcp { host, port } => write!(f, "{}", format_host_port(host, *port)),
+            SocketEndpoint::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
 }
 
 impl ForwardingSpec {
@@ -19,61 +54,263 @@ impl ForwardingSpec {
     /// Supported formats:
     /// - "port:host:port" (e.g., "8080:127.0.0.1:8080")
     /// - "address:port:host:port" (e.g., "0.0.0.0:8080:127.0.0.1:8080")
-    /// - IPv6 addresses must be enclosed in brackets, e.g., "[::1]:80:localhost:80"
+    /// - "port" or "address:port" for dynamic SOCKS forwarding (e.g., "1080", "0.0.0.0:1080")
+    /// - A bind port of `auto` or `0` picks a free port at launch, e.g. "auto:127.0.0.1:8080"
+    /// - IPv6 addresses must be enclosed in brackets on either side, e.g.
+    ///   "[::1]:8080:[2001:db8::1]:80"
+    /// - Either side may be a Unix socket path starting with `/`, e.g.
+    ///   "/tmp/docker.sock:/var/run/docker.sock" or "5432:/run/postgresql/.s.PGSQL.5432"
+    ///
+    /// Any format may be prefixed with `L:`, `R:` or `D:` to override the default mode
+    /// for this forward, e.g. "R:9000:127.0.0.1:9000".
     pub fn parse(spec: &str) -> StunResult<Self> {
-        // Parse from right to left to handle IPv6 addresses correctly
-        // Format is always: [bind_addr:]bind_port:remote_host:remote_port
+        if Self::expand_range(spec)?.is_some() {
+            return Err(StunError::Config(format!(
+                "Forwarding specification '{spec}' is a port range; use ForwardingSpec::parse_range"
+            )));
+        }
+        let (mode, body) = Self::split_mode_prefix(spec);
+        let mut parsed = Self::parse_unprefixed(body)?;
+        if let Some(mode) = mode
+            && (mode == ForwardingMode::Dynamic) != parsed.is_dynamic()
+        {
+            return Err(StunError::Config(format!(
+                "Forwarding specification '{spec}' does not fit its {mode:?} prefix"
+            )));
+        }
+        parsed.mode = mode;
+        Ok(parsed)
+    }
 
-        let last_colon = spec.rfind(':').ok_or_else(|| {
-            StunError::Config(format!("Invalid forwarding specification: {spec}"))
-        })?;
+    /// Parse a specification that may contain port ranges into one spec per port.
+    ///
+    /// A range such as "9900-9910:127.0.0.1:9900-9910" pairs the bind ports with the
+    /// target ports in order, so both ranges must have the same length. Dynamic specs
+    /// take a range on the bind port alone, e.g. "D:1080-1083". Specs without a range
+    /// yield a single element.
+    pub fn parse_range(spec: &str) -> StunResult<Vec<Self>> {
+        match Self::expand_range(spec)? {
+            Some(specs) => specs.iter().map(|spec| Self::parse(spec)).collect(),
+            None => Ok(vec![Self::parse(spec)?]),
+        }
+    }
 
-        let remote_port_str = &spec[last_colon + 1..];
-        let remote_port = remote_port_str
-            .parse::<u16>()
-            .map_err(|_| StunError::Config(format!("Invalid remote port: {remote_port_str}")))?;
+    /// Expand the port ranges of a specification into individual spec strings,
+    /// or None when it has no range
+    pub fn expand_range(spec: &str) -> StunResult<Option<Vec<String>>> {
+        let (_, body) = Self::split_mode_prefix(spec);
+        let prefix = &spec[..spec.len() - body.len()];
+        let parts = SpecParts::split(body)?;
+        if parts.uses_unix_socket() {
+            return Ok(None);
+        }
 
-        let rest = &spec[..last_colon];
-        // rest is now "[bind_addr:]bind_port:remote_host"
+        let invalid = |reason: &str| {
+            StunError::Config(format!(
+                "Invalid port range in forwarding specification '{spec}': {reason}"
+            ))
+        };
+        let bind_range = parse_port_range(parts.bind).map_err(|e| invalid(&e))?;
+        let target_range = match parts.target_port {
+            Some(port) => parse_port_range(port).map_err(|e| invalid(&e))?,
+            None => None,
+        };
 
-        let second_last_colon = rest.rfind(':').ok_or_else(|| {
-            StunError::Config(format!("Invalid forwarding specification: {spec}"))
-        })?;
+        // Rebuild from the raw fields so bracketed addresses stay as written
+        let render = |bind: u16, target: Option<u16>| {
+            let mut rendered = prefix.to_string();
+            if let Some(address) = parts.bind_address {
+                rendered.push_str(address);
+                rendered.push(':');
+            }
+            rendered.push_str(&bind.to_string());
+            if let (Some(host), Some(port)) = (parts.target_host, target) {
+                rendered.push_str(&format!(":{host}:{port}"));
+            }
+            rendered
+        };
+        match (bind_range, target_range, parts.target_host) {
+            (None, None, _) => Ok(None),
+            (Some(bind), None, None) => Ok(Some(bind.map(|port| render(port, None)).collect())),
+            (Some(bind), Some(target), Some(_)) if bind.len() == target.len() => Ok(Some(
+                bind.zip(target)
+                    .map(|(port, target)| render(port, Some(target)))
+                    .collect(),
+            )),
+            (Some(_), Some(_), _) => Err(invalid("ranges have different lengths")),
+            _ => Err(invalid("both the bind and the target port must be ranges")),
+        }
+    }
+
+    /// Split an optional `L:`, `R:` or `D:` mode prefix off a specification string
+    pub fn split_mode_prefix(spec: &str) -> (Option<ForwardingMode>, &str) {
+        let mode = match spec.get(..2) {
+            Some("L:") => ForwardingMode::Local,
+            Some("R:") => ForwardingMode::Remote,
+            Some("D:") => ForwardingMode::Dynamic,
+            _ => return (None, spec),
+        };
+        (Some(mode), &spec[2..])
+    }
 
-        let remote_host = &rest[second_last_colon + 1..];
-        let rest2 = &rest[..second_last_colon];
-        // rest2 is now "[bind_addr:]bind_port"
+    fn parse_unprefixed(spec: &str) -> StunResult<Self> {
+        let parts = SpecParts::split(spec)?;
+        let invalid = || StunError::Config(format!("Invalid forwarding specification: {spec}"));
 
-        let (bind_addr, bind_port_str) = if let Some(idx) = rest2.rfind(':') {
-            // Has bind address
-            (Some(rest2[..idx].to_string()), &rest2[idx + 1..])
-        } else {
-            // No bind address
-            (None, rest2)
+        let mut parsed = ForwardingSpec {
+            bind_address: parts.bind_address.map(parse_host).transpose()?,
+            bind_port: 0,
+            remote_host: String::new(),
+            remote_port: 0,
+            mode: None,
+            bind_socket: None,
+            remote_socket: None,
         };
+        if parts.bind.starts_with('/') {
+            // A socket path on its own would be a dynamic forward, which ssh cannot bind
+            if parts.target_host.is_none() {
+                return Err(invalid());
+            }
+            parsed.bind_socket = Some(parts.bind.to_string());
+        } else {
+            parsed.bind_port = parse_bind_port(parts.bind)?;
+        }
 
-        let bind_port = bind_port_str
-            .parse::<u16>()
-            .map_err(|_| StunError::Config(format!("Invalid bind port: {bind_port_str}")))?;
+        match (parts.target_host, parts.target_port) {
+            (None, _) => {}
+            (Some(path), None) if path.starts_with('/') => {
+                parsed.remote_socket = Some(path.to_string());
+            }
+            (Some(host), Some(port)) => {
+                parsed.remote_host = parse_host(host)?;
+                parsed.remote_port = port
+                    .parse::<u16>()
+                    .map_err(|_| StunError::Config(format!("Invalid remote port: {port}")))?;
+            }
+            (Some(_), None) => return Err(invalid()),
+        }
+        Ok(parsed)
+    }
 
-        Ok(ForwardingSpec {
-            bind_address: bind_addr,
-            bind_port,
-            remote_host: remote_host.to_string(),
-            remote_port,
-        })
+    /// Parse a dynamic forwarding specification: "[bind_addr:]port"
+    pub fn parse_dynamic(spec: &str) -> StunResult<Self> {
+        let parsed = Self::parse_unprefixed(spec)?;
+        if !parsed.is_dynamic() {
+            return Err(StunError::Config(format!(
+                "Invalid dynamic forwarding specification '{spec}'. Expected format: [bind_addr:]port"
+            )));
+        }
+        Ok(parsed)
+    }
+
+    /// Returns true for a dynamic (SOCKS) forward, which has no fixed target
+    pub fn is_dynamic(&self) -> bool {
+        self.remote_host.is_empty() && self.remote_socket.is_none()
+    }
+
+    /// Whether stun picks a free bind port before the forward is opened
+    pub fn is_auto_port(&self) -> bool {
+        self.bind_socket.is_none() && self.bind_port == 0
+    }
+
+    /// Copy of this spec listening on `port` instead of the configured bind port
+    pub fn with_bind_port(&self, port: u16) -> Self {
+        Self {
+            bind_port: port,
+            ..self.clone()
+        }
+    }
+
+    /// Whether either side of the forward is a Unix socket
+    pub fn uses_unix_socket(&self) -> bool {
+        self.bind_socket.is_some() || self.remote_socket.is_some()
+    }
+
+    /// Address or socket the forward listens on
+    pub fn bind_endpoint(&self) -> SocketEndpoint {
+        match &self.bind_socket {
+            Some(path) => SocketEndpoint::Unix(PathBuf::from(path)),
+            None => SocketEndpoint::Tcp {
+                host: self.effective_bind_address().to_string(),
+                port: self.bind_port,
+            },
+        }
+    }
+
+    /// Address or socket connections are forwarded to; None for dynamic forwards
+    pub fn target_endpoint(&self) -> Option<SocketEndpoint> {
+        match &self.remote_socket {
+            Some(path) => Some(SocketEndpoint::Unix(PathBuf::from(path))),
+            None if self.is_dynamic() => None,
+            None => Some(SocketEndpoint::Tcp {
+                host: self.remote_host.clone(),
+                port: self.remote_port,
+            }),
+        }
+    }
+
+    /// Mode of this forward, falling back to `default` when the spec has no prefix
+    pub fn mode_or(&self, default: ForwardingMode) -> ForwardingMode {
+        self.mode.unwrap_or(default)
+    }
+
+    /// Resolve the mode against `default` and check that the spec fits it:
+    /// dynamic forwards take `[bind_addr:]port`, local and remote forwards need a target,
+    /// and only local and dynamic forwards may use an automatic bind port.
+    pub fn resolve_mode(&self, default: ForwardingMode) -> StunResult<ForwardingMode> {
+        let mode = self.mode_or(default);
+        if (mode == ForwardingMode::Dynamic) != self.is_dynamic() {
+            let expected = if mode == ForwardingMode::Dynamic {
+                "[bind_addr:]port"
+            } else {
+                "[bind_addr:]port:host:port"
+            };
+            return Err(StunError::Config(format!(
+                "Invalid forwarding specification '{}'. Expected format for {mode:?} mode: {expected}",
+                self.to_spec_string()
+            )));
+        }
+        // The port would be picked on the SSH server, where stun cannot learn it
+        if mode == ForwardingMode::Remote && self.is_auto_port() {
+            return Err(StunError::Config(format!(
+                "Forwarding specification '{}' cannot use an automatic bind port in remote mode",
+                self.to_spec_string()
+            )));
+        }
+        Ok(mode)
+    }
+
+    /// Specification string as written in the config, including any mode prefix.
+    /// An automatic bind port is written as `auto`.
+    pub fn to_spec_string(&self) -> String {
+        let prefix = self.mode.map_or("", |mode| mode.spec_prefix());
+        let port = if self.is_auto_port() {
+            AUTO_PORT.to_string()
+        } else {
+            self.bind_port.to_string()
+        };
+        format!("{prefix}{}", self.format_with_port(&port))
     }
 
     /// Convert to SSH forwarding argument format
     pub fn to_ssh_arg(&self) -> String {
-        match &self.bind_address {
-            Some(addr) => format!(
-                "{}:{}:{}:{}",
-                addr, self.bind_port, self.remote_host, self.remote_port
-            ),
+        self.format_with_port(&self.bind_port.to_string())
+    }
+
+    fn format_with_port(&self, port: &str) -> String {
+        let bind = match (&self.bind_socket, &self.bind_address) {
+            (Some(path), _) => path.clone(),
+            (None, Some(addr)) => format!("{}:{port}", format_host(addr)),
+            (None, None) => port.to_string(),
+        };
+        if self.is_dynamic() {
+            return bind;
+        }
+        match &self.remote_socket {
+            Some(path) => format!("{bind}:{path}"),
             None => format!(
-                "{}:{}:{}",
-                self.bind_port, self.remote_host, self.remote_port
+                "{bind}:{}",
+                format_host_port(&self.remote_host, self.remote_port)
             ),
         }
     }
@@ -84,8 +321,153 @@ impl ForwardingSpec {
     }
 }
 
+/// Raw fields of a spec body, before hosts and ports are validated
+struct SpecParts<'a> {
+    /// Bind address as written, possibly bracketed
+    bind_address: Option<&'a str>,
+    /// Bind port, port range or Unix socket path
+    bind: &'a str,
+    /// Target host as written, or a Unix socket path; None for dynamic forwards
+    target_host: Option<&'a str>,
+    /// Target port or port range; None for dynamic forwards and socket targets
+    target_port: Option<&'a str>,
+}
+
+impl<'a> SpecParts<'a> {
+    /// Assign the fields of `spec` to the bind and target sides
+    fn split(spec: &'a str) -> StunResult<Self> {
+        let is_path = |field: &str| field.starts_with('/');
+        let parts = |bind_address, bind, target_host, target_port| Self {
+            bind_address,
+            bind,
+            target_host,
+            target_port,
+        };
+
+        Ok(match split_fields(spec)?.as_slice() {
+            [bind] => parts(None, bind, None, None),
+            [bind, target] if is_path(bind) || is_path(target) => {
+                parts(None, bind, Some(target), None)
+            }
+            [address, bind] => parts(Some(address), bind, None, None),
+            [address, bind, target] if is_path(target) && !is_path(bind) => {
+                parts(Some(address), bind, Some(target), None)
+            }
+            [bind, host, port] => parts(None, bind, Some(host), Some(port)),
+            [address, bind, host, port] => parts(Some(address), bind, Some(host), Some(port)),
+            _ => {
+                return Err(StunError::Config(format!(
+                    "Invalid forwarding specification '{spec}'. Expected format: [bind_addr:]port:host:port"
+                )));
+            }
+        })
+    }
+
+    fn uses_unix_socket(&self) -> bool {
+        self.bind.starts_with('/') || self.target_host.is_some_and(|host| host.starts_with('/'))
+    }
+}
+
+/// Split on the colons outside `[...]`. A Unix socket path after the first field is
+/// the target and runs to the end, so it may contain colons itself.
+fn split_fields(spec: &str) -> StunResult<Vec<&str>> {
+    let invalid = |reason: &str| {
+        StunError::Config(format!(
+            "Invalid forwarding specification '{spec}': {reason}"
+        ))
+    };
+
+    let mut fields = Vec::new();
+    let mut rest = spec;
+    loop {
+        if !fields.is_empty() && rest.starts_with('/') {
+            fields.push(rest);
+            return Ok(fields);
+        }
+        let end = if rest.starts_with('[') {
+            rest.find(']').ok_or_else(|| invalid("unclosed '['"))? + 1
+        } else {
+            rest.find(':').unwrap_or(rest.len())
+        };
+        let (field, tail) = rest.split_at(end);
+        fields.push(field);
+        match tail.strip_prefix(':') {
+            Some(tail) => rest = tail,
+            None if tail.is_empty() => return Ok(fields),
+            None => return Err(invalid("expected ':' after ']'")),
+        }
+    }
+}
+
+/// Host of a spec field with IPv6 brackets removed
+fn parse_host(field: &str) -> StunResult<String> {
+    let host = match field.strip_prefix('[') {
+        Some(inner) => inner.strip_suffix(']').unwrap_or(inner),
+        None => field,
+    };
+    if host.is_empty() || host.contains(['[', ']']) {
+        return Err(StunError::Config(format!("Invalid host '{field}'")));
+    }
+    Ok(host.to_string())
+}
+
+/// Parse `host:port`, where an IPv6 host is enclosed in brackets: `[::1]:80`
+pub fn parse_host_port(text: &str) -> StunResult<(String, u16)> {
+    let invalid = || StunError::Config(format!("Invalid address '{text}', expected host:port"));
+    let fields = split_fields(text).map_err(|_| invalid())?;
+    let [host, port] = fields.as_slice() else {
+        return Err(invalid());
+    };
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| StunError::Config(format!("Invalid port '{port}' in address '{text}'")))?;
+    Ok((parse_host(host).map_err(|_| invalid())?, port))
+}
+
+/// Host as written in `host:port` forms, bracketing IPv6 addresses
+pub fn format_host(host: &str) -> Cow<'_, str> {
+    if host.contains(':') {
+        Cow::Owned(format!("[{host}]"))
+    } else {
+        Cow::Borrowed(host)
+    }
+}
+
+/// `host:port`, bracketing IPv6 addresses
+pub fn format_host_port(host: &str, port: u16) -> String {
+    format!("{}:{port}", format_host(host))
+}
+
+/// Parse a bind port; `auto` and `0` both select a free port at launch
+fn parse_bind_port(port: &str) -> StunResult<u16> {
+    if port == AUTO_PORT {
+        return Ok(0);
+    }
+    port.parse::<u16>()
+        .map_err(|_| StunError::Config(format!("Invalid bind port: {port}")))
+}
+
+/// Parse "start-end" into its inclusive port range; None for a single port
+fn parse_port_range(field: &str) -> Result<Option<std::ops::RangeInclusive<u16>>, String> {
+    let Some((start, end)) = field.split_once('-') else {
+        return Ok(None);
+    };
+    let port = |value: &str| {
+        value
+            .parse::<u16>()
+            .map_err(|_| format!("invalid port '{value}'"))
+    };
+    let (start, end) = (port(start)?, port(end)?);
+    if start > end {
+        return Err(format!("{start}-{end} is empty"));
+    }
+    Ok(Some(start..=end))
+}
+
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -115,6 +497,25 @@ mod tests {
         assert_eq!(spec2.to_ssh_arg(), "0.0.0.0:8080:192.168.1.10:9000");
     }
 
+    #[test]
+    fn test_parse_auto_port() {
+        let spec = ForwardingSpec::parse("auto:127.0.0.1:9000").unwrap();
+        assert!(spec.is_auto_port());
+        assert_eq!(spec, ForwardingSpec::parse("0:127.0.0.1:9000").unwrap());
+        assert_eq!(spec.to_spec_string(), "auto:127.0.0.1:9000");
+        assert_eq!(
+            spec.with_bind_port(41000).to_ssh_arg(),
+            "41000:127.0.0.1:9000"
+        );
+
+        let spec = ForwardingSpec::parse("D:127.0.0.1:auto").unwrap();
+        assert_eq!(spec.to_spec_string(), "D:127.0.0.1:auto");
+        assert!(spec.resolve_mode(ForwardingMode::Local).is_ok());
+
+        let spec = ForwardingSpec::parse("R:auto:127.0.0.1:9000").unwrap();
+        assert!(spec.resolve_mode(ForwardingMode::Local).is_err());
+    }
+
     #[test]
     fn test_effective_bind_address() {
         let spec1 = ForwardingSpec::parse("8080:127.0.0.1:9000").unwrap();
@@ -124,6 +525,233 @@ mod tests {
         assert_eq!(spec2.effective_bind_address(), "0.0.0.0");
     }
 
+    #[test]
+    fn test_parse_dynamic_spec() {
+        let spec = ForwardingSpec::parse("1080").unwrap();
+        assert!(spec.is_dynamic());
+        assert_eq!(spec.bind_address, None);
+        assert_eq!(spec.bind_port, 1080);
+        assert_eq!(spec.to_ssh_arg(), "1080");
+
+        let spec = ForwardingSpec::parse("0.0.0.0:1080").unwrap();
+        assert!(spec.is_dynamic());
+        assert_eq!(spec.effective_bind_address(), "0.0.0.0");
+        assert_eq!(spec.to_ssh_arg(), "0.0.0.0:1080");
+
+        let spec = ForwardingSpec::parse("[::1]:1080").unwrap();
+        assert_eq!(spec.bind_address, Some("::1".to_string()));
+        assert_eq!(spec.bind_port, 1080);
+        assert_eq!(spec.to_ssh_arg(), "[::1]:1080");
+
+        assert!(
+            !ForwardingSpec::parse("8080:127.0.0.1:9000")
+                .unwrap()
+                .is_dynamic()
+        );
+        assert!(ForwardingSpec::parse(":1080").is_err());
+    }
+
+    #[test]
+    fn test_parse_mode_prefix() {
+        let spec = ForwardingSpec::parse("R:0.0.0.0:9000:127.0.0.1:9000").unwrap();
+        assert_eq!(spec.mode, Some(ForwardingMode::Remote));
+        assert_eq!(spec.to_ssh_arg(), "0.0.0.0:9000:127.0.0.1:9000");
+        assert_eq!(spec.to_spec_string(), "R:0.0.0.0:9000:127.0.0.1:9000");
+        assert_eq!(spec.mode_or(ForwardingMode::Local), ForwardingMode::Remote);
+
+        let spec = ForwardingSpec::parse("D:1080").unwrap();
+        assert_eq!(spec.mode, Some(ForwardingMode::Dynamic));
+        assert!(spec.is_dynamic());
+
+        let spec = ForwardingSpec::parse("8080:127.0.0.1:9000").unwrap();
+        assert_eq!(spec.mode, None);
+        assert_eq!(spec.mode_or(ForwardingMode::Remote), ForwardingMode::Remote);
+        assert!(spec.resolve_mode(ForwardingMode::Dynamic).is_err());
+
+        assert!(ForwardingSpec::parse("L:1080").is_err());
+        assert!(ForwardingSpec::parse("D:8080:127.0.0.1:9000").is_err());
+    }
+
+    #[test]
+    fn test_parse_unix_socket_specs() {
+        let spec = ForwardingSpec::parse("/tmp/docker.sock:/var/run/docker.sock").unwrap();
+        assert_eq!(spec.bind_socket.as_deref(), Some("/tmp/docker.sock"));
+        assert_eq!(spec.remote_socket.as_deref(), Some("/var/run/docker.sock"));
+        assert!(!spec.is_dynamic());
+        assert_eq!(
+            spec.bind_endpoint(),
+            SocketEndpoint::Unix(PathBuf::from("/tmp/docker.sock"))
+        );
+
+        let spec = ForwardingSpec::parse("127.0.0.1:5432:/run/postgresql/.s.PGSQL.5432").unwrap();
+        assert_eq!(spec.bind_port, 5432);
+        assert_eq!(spec.effective_bind_address(), "127.0.0.1");
+        assert_eq!(
+            spec.target_endpoint(),
+            Some(SocketEndpoint::Unix(PathBuf::from(
+                "/run/postgresql/.s.PGSQL.5432"
+            )))
+        );
+
+        let spec = ForwardingSpec::parse("R:/tmp/app.sock:localhost:8080").unwrap();
+        assert_eq!(spec.mode, Some(ForwardingMode::Remote));
+        assert_eq!(spec.remote_host, "localhost");
+        assert_eq!(spec.remote_port, 8080);
+
+        for text in [
+            "/tmp/docker.sock:/var/run/docker.sock",
+            "5432:/run/postgresql/.s.PGSQL.5432",
+            "0.0.0.0:5432:/run/pg.sock",
+            "R:/tmp/app.sock:localhost:8080",
+        ] {
+            let spec = ForwardingSpec::parse(text).unwrap();
+            assert_eq!(spec.to_spec_string(), text);
+            assert_eq!(ForwardingSpec::parse(&spec.to_spec_string()).unwrap(), spec);
+        }
+
+        assert!(ForwardingSpec::parse("/tmp/only.sock").is_err());
+        assert!(ForwardingSpec::parse("/tmp/a.sock:host").is_err());
+        assert!(ForwardingSpec::parse("D:/tmp/a.sock:/tmp/b.sock").is_err());
+    }
+
+    #[test]
+    fn test_parse_port_ranges() {
+        let specs = ForwardingSpec::parse_range("9900-9902:127.0.0.1:9900-9902").unwrap();
+        let args: Vec<String> = specs.iter().map(ForwardingSpec::to_ssh_arg).collect();
+        assert_eq!(
+            args,
+            [
+                "9900:127.0.0.1:9900",
+                "9901:127.0.0.1:9901",
+                "9902:127.0.0.1:9902"
+            ]
+        );
+
+        let specs = ForwardingSpec::parse_range("R:0.0.0.0:8000-8001:app-1:9000-9001").unwrap();
+        assert_eq!(specs[1].to_spec_string(), "R:0.0.0.0:8001:app-1:9001");
+
+        let specs = ForwardingSpec::parse_range("D:127.0.0.1:1080-1081").unwrap();
+        assert_eq!(specs[1].to_spec_string(), "D:127.0.0.1:1081");
+
+        // Specs without a range, including hostnames with dashes, are not expanded
+        assert_eq!(
+            ForwardingSpec::expand_range("8080:my-host:80").unwrap(),
+            None
+        );
+        assert_eq!(
+            ForwardingSpec::parse_range("8080:my-host:80")
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert!(ForwardingSpec::parse_range("9900-9910:127.0.0.1:9900-9905").is_err());
+        assert!(ForwardingSpec::parse_range("9900-9910:127.0.0.1:9900").is_err());
+        assert!(ForwardingSpec::parse_range("9910-9900:127.0.0.1:9910-9900").is_err());
+        assert!(ForwardingSpec::parse("9900-9901:127.0.0.1:9900-9901").is_err());
+    }
+
+    #[test]
+    fn test_parse_ipv6_specs() {
+        let spec = ForwardingSpec::parse("8080:[::1]:80").unwrap();
+        assert_eq!(spec.bind_address, None);
+        assert_eq!(spec.remote_host, "::1");
+        assert_eq!(spec.remote_port, 80);
+
+        let spec = ForwardingSpec::parse("[::1]:8080:[2001:db8::1]:80").unwrap();
+        assert_eq!(spec.effective_bind_address(), "::1");
+        assert_eq!(spec.remote_host, "2001:db8::1");
+        assert_eq!(spec.to_ssh_arg(), "[::1]:8080:[2001:db8::1]:80");
+        assert_eq!(spec.bind_endpoint().to_string(), "[::1]:8080");
+
+        let spec = ForwardingSpec::parse("[::1]:5432:/run/pg.sock").unwrap();
+        assert_eq!(spec.effective_bind_address(), "::1");
+
+        let specs = ForwardingSpec::parse_range("[::1]:9900-9901:[fd00::2]:9900-9901").unwrap();
+        assert_eq!(specs[1].to_ssh_arg(), "[::1]:9901:[fd00::2]:9901");
+
+        assert_eq!(
+            parse_host_port("[fe80::1]:22").unwrap(),
+            ("fe80::1".to_string(), 22)
+        );
+        assert!(parse_host_port("fe80::1:22").is_err());
+        assert!(ForwardingSpec::parse("[::1:8080:localhost:80").is_err());
+        assert!(ForwardingSpec::parse("[::1]x:8080:localhost:80").is_err());
+        assert!(ForwardingSpec::parse("8080:[]:80").is_err());
+        assert!(ForwardingSpec::parse("8080:::1:80").is_err());
+    }
+
+    fn host() -> impl Strategy<Value = String> {
+        prop_oneof![
+            any::<std::net::Ipv4Addr>().prop_map(|ip| ip.to_string()),
+            any::<std::net::Ipv6Addr>().prop_map(|ip| ip.to_string()),
+            "[a-z][a-z0-9-]{0,12}(\\.[a-z][a-z0-9-]{0,12}){0,2}",
+        ]
+    }
+
+    fn spec() -> impl Strategy<Value = ForwardingSpec> {
+        let mode = prop_oneof![
+            Just(None),
+            Just(Some(ForwardingMode::Local)),
+            Just(Some(ForwardingMode::Remote)),
+        ];
+        let target = prop_oneof![
+            (host(), any::<u16>()).prop_map(|(host, port)| (host, port, None)),
+            "(/[a-z0-9._-]{1,8}){1,3}".prop_map(|path| (String::new(), 0, Some(path))),
+        ];
+        (proptest::option::of(host()), any::<u16>(), target, mode).prop_map(
+            |(bind_address, bind_port, (remote_host, remote_port, remote_socket), mode)| {
+                ForwardingSpec {
+                    bind_address,
+                    bind_port,
+                    remote_host,
+                    remote_port,
+                    mode,
+                    bind_socket: None,
+                    remote_socket,
+                }
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_spec_round_trips(spec in spec()) {
+            let parsed = ForwardingSpec::parse(&spec.to_spec_string()).unwrap();
+            prop_assert_eq!(&parsed, &spec);
+            let reparsed = ForwardingSpec::parse(&parsed.to_ssh_arg()).unwrap();
+            prop_assert_eq!(reparsed.bind_address, spec.bind_address);
+            prop_assert_eq!(reparsed.remote_host, spec.remote_host);
+        }
+
+        #[test]
+        fn prop_dynamic_spec_round_trips(bind_address in proptest::option::of(host()), port: u16) {
+            let spec = ForwardingSpec {
+                bind_address,
+                bind_port: port,
+                remote_host: String::new(),
+                remote_port: 0,
+                mode: Some(ForwardingMode::Dynamic),
+                bind_socket: None,
+                remote_socket: None,
+            };
+            prop_assert_eq!(ForwardingSpec::parse(&spec.to_spec_string()).unwrap(), spec);
+        }
+
+        #[test]
+        fn prop_host_port_round_trips(host in host(), port: u16) {
+            let text = format_host_port(&host, port);
+            prop_assert_eq!(parse_host_port(&text).unwrap(), (host, port));
+        }
+
+        #[test]
+        fn prop_parse_never_panics(text in "[\\[\\]:/0-9a-f.LRD-]{0,24}") {
+            if let Ok(spec) = ForwardingSpec::parse(&text) {
+                prop_assert_eq!(ForwardingSpec::parse(&spec.to_spec_string()).unwrap(), spec);
+            }
+        }
+    }
+
     #[test]
     fn test_invalid_specs() {
         assert!(ForwardingSpec::parse("invalid").is_err());
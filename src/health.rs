@@ -1,9 +1,18 @@
-use std::time::Duration;
+use std::{net::IpAddr, time::Duration};
 
-use tokio::{net::TcpStream, time::timeout};
-use tracing::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+use tracing::{debug, error, warn};
 
-use crate::forwarding::ForwardingSpec;
+use crate::{
+    forwarding::{ForwardingSpec, SocketEndpoint, format_host_port},
+    host_key,
+    ssh::read_stderr,
+};
 
 /// Health checker for port forwarding connections
 #[derive(Debug, Clone)]
@@ -20,13 +29,14 @@ impl HealthChecker {
         }
     }
 
-    /// Check if a forwarding connection is healthy by attempting to connect
+    /// Check if a forwarding connection is healthy by connecting to its bind side,
+    /// a TCP address or a Unix socket
     pub async fn check_forwarding(&self, spec: &ForwardingSpec) -> bool {
-        let address = format!("{}:{}", spec.effective_bind_address(), spec.bind_port);
+        let address = spec.bind_endpoint();
 
         debug!("Health checking connection to {}", address);
 
-        match timeout(self.timeout, TcpStream::connect(&address)).await {
+        match timeout(self.timeout, connect(&address)).await {
             Ok(Ok(_)) => {
                 debug!("Health check successful for {}", address);
                 true
@@ -42,12 +52,47 @@ impl HealthChecker {
         }
     }
 
+    /// Check a dynamic forward by performing a SOCKS5 handshake with the proxy.
+    /// With a `target`, also asks the proxy to CONNECT to it through the SSH server.
+    pub async fn check_socks_proxy(
+        &self,
+        spec: &ForwardingSpec,
+        target: Option<(String, u16)>,
+    ) -> bool {
+        let address = format_host_port(spec.effective_bind_address(), spec.bind_port);
+
+        debug!("SOCKS5 health check against {}", address);
+
+        match timeout(self.timeout, socks5_handshake(&address, target)).await {
+            Ok(Ok(())) => {
+                debug!("SOCKS5 health check successful for {}", address);
+                true
+            }
+            Ok(Err(e)) => {
+                warn!("SOCKS5 health check failed for {}: {}", address, e);
+                false
+            }
+            Err(_) => {
+                warn!("SOCKS5 health check timed out for {}", address);
+                false
+            }
+        }
+    }
+
     /// Check if an SSH process is responding by attempting to write to stdin
     pub async fn check_ssh_process(&self, process: &mut tokio::process::Child) -> bool {
         // Check if the process is still running
         match process.try_wait() {
             Ok(Some(status)) => {
-                warn!("SSH process exited with status: {}", status);
+                let stderr = read_stderr(process).await;
+                match host_key::rejection_error(&stderr) {
+                    Some(e) => error!("SSH process exited: {}", e),
+                    None => warn!(
+                        "SSH process exited with status {}: {}",
+                        status,
+                        stderr.trim()
+                    ),
+                }
                 false
             }
             Ok(None) => {
@@ -62,8 +107,78 @@ impl HealthChecker {
     }
 }
 
+async fn connect(endpoint: &SocketEndpoint) -> std::io::Result<()> {
+    match endpoint {
+        SocketEndpoint::Tcp { host, port } => {
+            TcpStream::connect((host.as_str(), *port)).await?;
+        }
+        #[cfg(unix)]
+        SocketEndpoint::Unix(path) => {
+            tokio::net::UnixStream::connect(path).await?;
+        }
+        #[cfg(not(unix))]
+        SocketEndpoint::Unix(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Negotiate "no authentication" and optionally CONNECT to `target`
+async fn socks5_handshake(address: &str, target: Option<(String, u16)>) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(address).await?;
+
+    stream.write_all(&[0x05, 0x01, 0x00]).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply != [0x05, 0x00] {
+        return Err(std::io::Error::other(format!(
+            "unexpected method selection {reply:02x?}"
+        )));
+    }
+
+    let Some((host, port)) = target else {
+        return Ok(());
+    };
+    // IP literals are sent as addresses, anything else as a name for the server to resolve
+    let mut request = vec![0x05, 0x01, 0x00];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(0x01);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(0x04);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let host_len = u8::try_from(host.len())
+                .map_err(|_| std::io::Error::other("probe host name is too long"))?;
+            request.extend_from_slice(&[0x03, host_len]);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply != [0x05, 0x00] {
+        return Err(std::io::Error::other(format!(
+            "CONNECT to {} failed with reply code {}",
+            format_host_port(&host, port),
+            reply[1]
+        )));
+    }
+    Ok(())
+}
+
 /// Health status for a tunnel
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TunnelHealth {
     /// Tunnel is healthy and functioning
     Healthy,
@@ -71,6 +186,10 @@ pub enum TunnelHealth {
     Down,
     /// Tunnel status is unknown (e.g., during startup)
     Unknown,
+    /// Tunnel was paused through the control API
+    Paused,
+    /// Tunnel cannot start and is not retried until it is restarted or reconfigured
+    Failed,
 }
 
 impl TunnelHealth {
@@ -111,4 +230,67 @@ mod tests {
 
         assert!(!result);
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_check() {
+        let path = std::env::temp_dir().join(format!("stun-health-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let spec =
+            ForwardingSpec::parse(&format!("{}:/var/run/docker.sock", path.display())).unwrap();
+        let checker = HealthChecker::new(1);
+
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        assert!(checker.check_forwarding(&spec).await);
+
+        drop(listener);
+        let _ = std::fs::remove_file(&path);
+        assert!(!checker.check_forwarding(&spec).await);
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_forwarding_check() {
+        // Hosts without IPv6 loopback have nothing to check
+        let Ok(listener) = tokio::net::TcpListener::bind("[::1]:0").await else {
+            return;
+        };
+        let port = listener.local_addr().unwrap().port();
+
+        let checker = HealthChecker::new(1);
+        let spec = ForwardingSpec::parse(&format!("[::1]:{port}:[2001:db8::1]:80")).unwrap();
+        assert!(checker.check_forwarding(&spec).await);
+    }
+
+    #[tokio::test]
+    async fn test_socks_proxy_check() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Minimal SOCKS5 server that accepts "no authentication" and any CONNECT
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream.write_all(&[0x05, 0x00]).await.unwrap();
+            let mut header = [0u8; 5];
+            stream.read_exact(&mut header).await.unwrap();
+            let mut rest = vec![0u8; header[4] as usize + 2];
+            stream.read_exact(&mut rest).await.unwrap();
+            stream
+                .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        });
+
+        let checker = HealthChecker::new(1);
+        let spec = ForwardingSpec::parse(&format!("127.0.0.1:{port}")).unwrap();
+        assert!(
+            checker
+                .check_socks_proxy(&spec, Some(("example.com".to_string(), 80)))
+                .await
+        );
+
+        // The listener is gone, so the next check cannot connect
+        assert!(!checker.check_socks_proxy(&spec, None).await);
+    }
 }
@@ -0,0 +1,254 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    process::Stdio,
+};
+
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{debug, info};
+
+use crate::{
+    config::{Config, HostKeyPolicy, ensure_private_dir, home_dir, private_dir_path},
+    error::{StunError, StunResult},
+};
+
+/// A public host key as listed in known_hosts or by `ssh-keyscan`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKey {
+    /// Key algorithm, e.g. `ssh-ed25519`
+    pub key_type: String,
+    /// Base64 encoded key blob
+    pub key: String,
+}
+
+impl HostKey {
+    /// Parse a `hosts keytype base64 [comment]` line.
+    /// Comments, blank lines and `@cert-authority`/`@revoked` lines yield None.
+    pub fn parse_line(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('@') {
+            return None;
+        }
+        let mut fields = line.split_whitespace().skip(1);
+        Some(Self {
+            key_type: fields.next()?.to_string(),
+            key: fields.next()?.to_string(),
+        })
+    }
+}
+
+/// Name under which OpenSSH records a host in known_hosts
+pub fn known_hosts_name(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{host}]:{port}")
+    }
+}
+
+/// known_hosts file used for strict and accept-new verification
+pub fn known_hosts_path(config: &Config) -> PathBuf {
+    match &config.remote.known_hosts {
+        Some(path) => PathBuf::from(path),
+        None => home_dir().join(".ssh").join("known_hosts"),
+    }
+}
+
+/// Per-remote file holding only the pinned keys the server presented. It lives in the
+/// private per-user directory, as whoever can write it decides which keys ssh trusts.
+pub fn pinned_known_hosts_path(config: &Config) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    config.remote.host.hash(&mut hasher);
+    config.remote.port.hash(&mut hasher);
+    private_dir_path().join(format!(
+        "stun-{}-{:x}.known_hosts",
+        std::process::id(),
+        hasher.finish()
+    ))
+}
+
+/// `-o` options that make ssh enforce the configured policy
+pub fn ssh_options(config: &Config) -> Vec<String> {
+    let (checking, known_hosts) = match &config.remote.host_key_policy {
+        HostKeyPolicy::Strict => ("yes", known_hosts_path(config)),
+        HostKeyPolicy::AcceptNew => ("accept-new", known_hosts_path(config)),
+        HostKeyPolicy::Pinned(_) => ("yes", pinned_known_hosts_path(config)),
+    };
+    vec![
+        "-o".to_string(),
+        format!("StrictHostKeyChecking={checking}"),
+        "-o".to_string(),
+        format!("UserKnownHostsFile={}", known_hosts.display()),
+    ]
+}
+
+/// Map ssh's report of a rejected host key to [`StunError::HostKey`].
+/// None when `stderr` shows some other failure.
+pub fn rejection_error(stderr: &str) -> Option<StunError> {
+    let reason = if stderr.contains("REMOTE HOST IDENTIFICATION HAS CHANGED") {
+        "presented a key that does not match known_hosts"
+    } else if stderr.contains("Host key verification failed") {
+        "presented a key that could not be verified"
+    } else {
+        return None;
+    };
+    Some(StunError::HostKey(format!("SSH server {reason}")))
+}
+
+/// Check a pinned host key before ssh connects.
+///
+/// The keys matching a pinned fingerprint are written to [`pinned_known_hosts_path`],
+/// which [`ssh_options`] points ssh at. Strict and accept-new need no check here:
+/// ssh enforces them with its own configuration, such as aliases and proxies from
+/// `~/.ssh/config`, and [`rejection_error`] reports what it rejects.
+pub async fn verify(config: &Config) -> StunResult<()> {
+    let HostKeyPolicy::Pinned(fingerprints) = &config.remote.host_key_policy else {
+        return Ok(());
+    };
+    let name = known_hosts_name(&config.remote.host, config.remote.port);
+
+    // ssh-keyscan cannot reach hosts behind jump hosts
+    if !config.remote.jump_hosts.is_empty() {
+        debug!(
+            "Leaving host key check of {} to ssh behind jump hosts",
+            name
+        );
+        return Ok(());
+    }
+    let presented = scan(config).await?;
+
+    let mut pinned = String::new();
+    let mut seen = Vec::new();
+    for key in &presented {
+        let fingerprint = fingerprint(&name, key).await?;
+        if fingerprints.contains(&fingerprint) {
+            pinned.push_str(&format!("{name} {} {}\n", key.key_type, key.key));
+        }
+        seen.push(fingerprint);
+    }
+    if pinned.is_empty() {
+        return Err(StunError::HostKey(format!(
+            "{name} presented {} but only {} are pinned",
+            seen.join(", "),
+            fingerprints.join(", ")
+        )));
+    }
+
+    let path = pinned_known_hosts_path(config);
+    if let Some(dir) = path.parent() {
+        ensure_private_dir(dir)?;
+    }
+    tokio::fs::write(&path, pinned).await?;
+
+    info!("Verified host key of {}", name);
+    Ok(())
+}
+
+/// Delete the file [`verify`] wrote for a pinned policy
+pub async fn remove_pinned(config: &Config) {
+    if matches!(config.remote.host_key_policy, HostKeyPolicy::Pinned(_)) {
+        let _ = tokio::fs::remove_file(pinned_known_hosts_path(config)).await;
+    }
+}
+
+/// Fetch the keys the server presents with `ssh-keyscan`
+async fn scan(config: &Config) -> StunResult<Vec<HostKey>> {
+    let output = Command::new("ssh-keyscan")
+        .args(["-T", &config.timeout.unwrap_or(2).max(1).to_string()])
+        .args(["-p", &config.remote.port.to_string()])
+        .arg(&config.remote.host)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| StunError::Ssh(format!("Failed to run ssh-keyscan: {e}")))?;
+
+    let keys: Vec<HostKey> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(HostKey::parse_line)
+        .collect();
+    if keys.is_empty() {
+        return Err(StunError::Network(format!(
+            "Could not retrieve host keys from {}:{}",
+            config.remote.host, config.remote.port
+        )));
+    }
+    debug!("Server presented {} host keys", keys.len());
+    Ok(keys)
+}
+
+/// SHA256 fingerprint of a key in `SHA256:<base64>` form
+async fn fingerprint(name: &str, key: &HostKey) -> StunResult<String> {
+    let mut child = Command::new("ssh-keygen")
+        .args(["-l", "-E", "sha256", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| StunError::Ssh(format!("Failed to run ssh-keygen: {e}")))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let line = format!("{name} {} {}\n", key.key_type, key.key);
+        stdin.write_all(line.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+
+    // Output: "256 SHA256:... name (ED25519)"
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(1)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            StunError::HostKey(format!(
+                "Could not fingerprint {} key of {name}",
+                key.key_type
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key_type: &str, key: &str) -> HostKey {
+        HostKey {
+            key_type: key_type.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            HostKey::parse_line("[example.com]:2222 ssh-ed25519 AAAAC3Nza comment"),
+            Some(key("ssh-ed25519", "AAAAC3Nza"))
+        );
+        assert_eq!(HostKey::parse_line("# example.com:22 SSH-2.0"), None);
+        assert_eq!(
+            HostKey::parse_line("@revoked example.com ssh-rsa AAAAB3"),
+            None
+        );
+        assert_eq!(known_hosts_name("example.com", 22), "example.com");
+        assert_eq!(known_hosts_name("example.com", 2222), "[example.com]:2222");
+    }
+
+    #[test]
+    fn test_rejection_error() {
+        let changed = "@@@@@@@@@@@\n@    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!     @\n\
+                       Host key verification failed.\n";
+        assert!(matches!(
+            rejection_error(changed),
+            Some(StunError::HostKey(message)) if message.contains("does not match")
+        ));
+        assert!(matches!(
+            rejection_error(
+                "No ED25519 host key is known for example.com\nHost key verification failed.\n"
+            ),
+            Some(StunError::HostKey(_))
+        ));
+        assert!(
+            rejection_error("ssh: connect to host example.com port 22: Connection refused")
+                .is_none()
+        );
+    }
+}
@@ -8,7 +8,8 @@
 //! - Local and remote SSH port forwarding
 //! - Connection health monitoring
 //! - Automatic reconnection on failure
-//! - JSON configuration support
+//! - Subscribable tunnel lifecycle events
+//! - JSON, TOML and YAML configuration support
 //! - Structured logging with tracing
 //!
 //! # Example
@@ -25,15 +26,14 @@
 //!             port: 22,
 //!             user: "username".to_string(),
 //!             key: None,
+//!             ..Default::default()
 //!         },
-//!         forwarding_list: vec![
-//!             "8080:127.0.0.1:8080".to_string(),
-//!             "9000:127.0.0.1:9000".to_string(),
-//!         ],
+//!         forwarding_list: vec!["8080:127.0.0.1:8080".into(), "9000:127.0.0.1:9000".into()],
 //!         timeout: Some(2),
 //!         remote_probes: None,
 //!         backoff_base_secs: Some(1),
 //!         backoff_max_secs: Some(30),
+//!         ..Default::default()
 //!     };
 //!
 //!     let mut manager = TunnelManager::new(config)?;
@@ -46,16 +46,33 @@
 //! ```
 
 pub mod config;
+#[cfg(unix)]
+pub mod control;
 pub mod error;
+pub mod events;
 pub mod forwarding;
 pub mod health;
+pub mod host_key;
 pub mod manager;
+#[cfg(feature = "native-ssh")]
+pub mod native;
+pub mod ports;
 pub mod ssh;
+pub mod ssh_config;
+pub mod status;
+pub mod transport;
+pub mod validation;
+pub mod watch;
 
-pub use config::{Config, ForwardingMode, RemoteConfig};
+pub use config::{
+    Config, Endpoint, FailoverPolicy, ForwardingMode, HostKeyPolicy, RemoteConfig, TransportKind,
+};
 pub use error::{StunError, StunResult};
-pub use manager::TunnelManager;
+pub use events::TunnelEvent;
+pub use manager::{ReloadSummary, TunnelManager};
+pub use status::TunnelStatus;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+pub use transport::TunnelTransport;
 
 /// Initialize logging with tracing
 pub fn init_logging() -> StunResult<()> {
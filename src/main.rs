@@ -1,7 +1,11 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
-use clap::{Arg, Command};
-use stun::{Config, TunnelManager};
+use clap::{Arg, ArgMatches, Command};
+use stun::{
+    Config, TunnelManager,
+    config::{ConfigFormat, ConfigOverrides, ConfigSource},
+    watch::ConfigWatcher,
+};
 use tokio::signal;
 use tracing::{error, info};
 
@@ -10,18 +14,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize logging
     stun::init_logging()?;
 
-    let matches = Command::new("stun")
+    let config_arg = Arg::new("config")
+        .short('c')
+        .long("config")
+        .value_name("FILE")
+        .help("Configuration file path")
+        .required(true);
+
+    let format_arg = Arg::new("format")
+        .long("format")
+        .value_name("FORMAT")
+        .value_parser(["json", "toml", "yaml"])
+        .help("Configuration file format (default: from the file extension)");
+
+    let command = Command::new("stun")
         .version("0.1.0")
         .author("akagi201")
         .about("SSH port forwarding and tunneling tool")
-        .arg(
-            Arg::new("config")
-                .short('c')
-                .long("config")
-                .value_name("FILE")
-                .help("Configuration file path")
-                .required(true),
-        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .arg(config_arg.clone())
+        .arg(format_arg.clone())
+        .args(override_args())
+        .arg(no_watch_arg())
         .arg(
             Arg::new("verbose")
                 .short('v')
@@ -29,28 +44,217 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .action(clap::ArgAction::Count)
                 .help("Increase logging verbosity"),
         )
-        .get_matches();
+        .subcommand(validate_command(config_arg.clone(), format_arg.clone()));
+
+    let command = command.subcommand(
+        Command::new("import-ssh-config")
+            .about("Print a configuration built from a Host in ~/.ssh/config")
+            .arg(Arg::new("host").required(true).help("Host alias to import"))
+            .arg(
+                Arg::new("ssh_config")
+                    .short('F')
+                    .long("ssh-config")
+                    .value_name("FILE")
+                    .help("ssh_config file to read (default: ~/.ssh/config)"),
+            )
+            .arg(
+                Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .value_name("FILE")
+                    .help("Write the configuration to FILE instead of stdout"),
+            )
+            .arg(
+                format_arg
+                    .clone()
+                    .help("Output format (default: from the output extension, JSON on stdout)"),
+            ),
+    );
+
+    #[cfg(unix)]
+    let command = command
+        .subcommand(
+            Command::new("daemon")
+                .about("Run tunnels and serve the control socket")
+                .arg(config_arg)
+                .arg(format_arg)
+                .args(override_args())
+                .arg(no_watch_arg())
+                .arg(socket_arg()),
+        )
+        .subcommand(ctl_command());
+
+    let matches = command.get_matches();
+
+    match matches.subcommand() {
+        Some(("validate", sub)) => validate_config(sub),
+        Some(("import-ssh-config", sub)) => import_ssh_config(sub),
+        #[cfg(unix)]
+        Some(("daemon", sub)) => run_daemon(sub).await,
+        #[cfg(unix)]
+        Some(("ctl", sub)) => run_ctl(sub).await,
+        _ => run_foreground(&matches).await,
+    }
+}
+
+fn validate_command(config_arg: Arg, format_arg: Arg) -> Command {
+    Command::new("validate")
+        .about("Check a configuration and list every problem found")
+        .arg(config_arg)
+        .arg(format_arg)
+        .args(override_args())
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(clap::ArgAction::SetTrue)
+                .help("Print the report as JSON"),
+        )
+}
+
+/// Value of `--format`, if given
+fn format_flag(matches: &ArgMatches) -> Result<Option<ConfigFormat>, Box<dyn std::error::Error>> {
+    Ok(matches
+        .get_one::<String>("format")
+        .map(|format| format.parse())
+        .transpose()?)
+}
 
+fn no_watch_arg() -> Arg {
+    Arg::new("no_watch")
+        .long("no-watch")
+        .action(clap::ArgAction::SetTrue)
+        .help("Do not reload when the configuration file changes (SIGHUP still reloads)")
+}
+
+/// Reload `manager` from `source` on file changes and SIGHUP until it stops
+fn spawn_watcher(
+    matches: &ArgMatches,
+    source: ConfigSource,
+    config: Config,
+    manager: &Arc<TunnelManager>,
+) {
+    let mut watcher = ConfigWatcher::new(source, Arc::clone(manager), config);
+    if matches.get_flag("no_watch") {
+        watcher = watcher.with_poll_interval(None);
+    }
+    tokio::spawn(async move {
+        if let Err(e) = watcher.run().await {
+            error!("Config watcher failed: {}", e);
+        }
+    });
+}
+
+/// Flags that override values from the configuration file
+fn override_args() -> [Arg; 7] {
+    [
+        Arg::new("mode")
+            .long("mode")
+            .value_name("MODE")
+            .value_parser(["local", "remote", "dynamic"])
+            .help("Forwarding mode [env: STUN_MODE]"),
+        Arg::new("host")
+            .long("host")
+            .value_name("HOST")
+            .help("Remote SSH host [env: STUN_HOST]"),
+        Arg::new("port")
+            .long("port")
+            .value_name("PORT")
+            .value_parser(clap::value_parser!(u16))
+            .help("Remote SSH port [env: STUN_PORT]"),
+        Arg::new("user")
+            .long("user")
+            .value_name("USER")
+            .help("Remote SSH user [env: STUN_USER]"),
+        Arg::new("key")
+            .long("key")
+            .value_name("FILE")
+            .help("SSH private key [env: STUN_KEY]"),
+        Arg::new("forward")
+            .long("forward")
+            .value_name("SPEC")
+            .action(clap::ArgAction::Append)
+            .help("Forwarding spec replacing the file's list, repeatable [env: STUN_FORWARD]"),
+        Arg::new("timeout")
+            .long("timeout")
+            .value_name("SECS")
+            .value_parser(clap::value_parser!(u64))
+            .help("Connection timeout in seconds [env: STUN_TIMEOUT]"),
+    ]
+}
+
+/// Overrides given as command line flags
+fn cli_overrides(matches: &ArgMatches) -> Result<ConfigOverrides, Box<dyn std::error::Error>> {
+    let string = |id: &str| matches.get_one::<String>(id).cloned();
+    Ok(ConfigOverrides {
+        mode: matches
+            .get_one::<String>("mode")
+            .map(|mode| mode.parse())
+            .transpose()?,
+        host: string("host"),
+        port: matches.get_one::<u16>("port").copied(),
+        user: string("user"),
+        key: string("key"),
+        forwards: matches
+            .get_many::<String>("forward")
+            .map(|specs| specs.cloned().collect())
+            .unwrap_or_default(),
+        timeout: matches.get_one::<u64>("timeout").copied(),
+    })
+}
+
+/// Configuration file referenced by `-c` and `--format`, layered under `STUN_*`
+/// environment variables and then command line flags
+fn config_source(matches: &ArgMatches) -> Result<ConfigSource, Box<dyn std::error::Error>> {
     let config_path = matches
         .get_one::<String>("config")
         .expect("config argument is required");
-    let config_path = PathBuf::from(config_path);
+    let overrides = ConfigOverrides::from_env()?.merge(cli_overrides(matches)?);
+    let mut source = ConfigSource::new(config_path).with_overrides(overrides);
+    if let Some(format) = format_flag(matches)? {
+        source = source.with_format(format);
+    }
+    Ok(source)
+}
+
+/// Load and log the configuration
+fn load_config(matches: &ArgMatches) -> Result<(ConfigSource, Config), Box<dyn std::error::Error>> {
+    let source = config_source(matches)?;
 
     // Load configuration
-    let config = Config::from_file(&config_path)?;
+    let config = source.load()?;
 
-    info!("Loaded configuration from {}", config_path.display());
-    info!("Mode: {:?}", config.mode);
     info!(
-        "Remote: {}@{}:{}",
-        config.remote.user, config.remote.host, config.remote.port
+        "Loaded {:?} configuration from {}",
+        source.format(),
+        source.path.display()
     );
+    if !source.overrides.is_empty() {
+        info!("Applied overrides: {:?}", source.overrides);
+    }
+    info!("Mode: {:?}", config.mode);
+    for name in config.remote_names() {
+        if let Some(remote) = config.remote_config(&name) {
+            info!(
+                "Remote {}: {}@{}:{}",
+                name, remote.user, remote.host, remote.port
+            );
+        }
+    }
     info!("Forwarding {} tunnels", config.forwarding_list.len());
 
+    Ok((source, config))
+}
+
+/// Run tunnels in the foreground until Ctrl+C
+async fn run_foreground(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let (config_source, config) = load_config(matches)?;
+
     // Create and start tunnel manager (background)
-    let mut manager = TunnelManager::new(config)?;
+    let mut manager = TunnelManager::new(config.clone())?;
     info!("Starting tunnel manager. Press Ctrl+C to stop.");
     let handle = manager.start_background().await?;
+    let manager = Arc::new(manager);
+    spawn_watcher(matches, config_source, config, &manager);
 
     // Wait for Ctrl+C
     signal::ctrl_c().await?;
@@ -65,3 +269,265 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Shutdown complete");
     Ok(())
 }
+
+/// Print every problem in the configuration; exits with status 1 if any is an error
+fn validate_config(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let source = config_source(matches)?;
+    let (config, mut report) = source.report();
+    if let Some(config) = &config {
+        report.extend(config.environment_report());
+    }
+
+    if matches.get_flag("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{report}");
+        let count = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+        let warnings = count(report.warnings().count(), "warning");
+        if report.has_errors() {
+            let errors = count(report.errors().count(), "error");
+            println!("{}: {errors}, {warnings}", source.path.display());
+        } else {
+            println!("{} is valid ({warnings})", source.path.display());
+        }
+    }
+
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Convert an ssh_config Host block into a stun configuration
+fn import_ssh_config(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let host = matches
+        .get_one::<String>("host")
+        .expect("host argument is required");
+    let ssh_config = matches
+        .get_one::<String>("ssh_config")
+        .map(PathBuf::from)
+        .unwrap_or_else(stun::ssh_config::default_path);
+
+    let config = Config::from_ssh_config(&ssh_config, host)?;
+
+    let format = format_flag(matches)?;
+    match matches.get_one::<String>("output") {
+        Some(output) => {
+            let format = format.unwrap_or_else(|| ConfigFormat::from_path(output.as_ref()));
+            config.to_file_with_format(output, format)?;
+            info!("Wrote configuration for {} to {}", host, output);
+        }
+        None => println!(
+            "{}",
+            config.serialize(format.unwrap_or_default())?.trim_end()
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn socket_arg() -> Arg {
+    Arg::new("socket")
+        .short('s')
+        .long("socket")
+        .value_name("PATH")
+        .help(
+            "Control socket path (default: $XDG_RUNTIME_DIR/stun.sock or /tmp/stun-$UID/stun.sock)",
+        )
+}
+
+#[cfg(unix)]
+fn socket_path(matches: &ArgMatches) -> PathBuf {
+    matches
+        .get_one::<String>("socket")
+        .map(PathBuf::from)
+        .unwrap_or_else(stun::control::default_socket_path)
+}
+
+#[cfg(unix)]
+fn ctl_command() -> Command {
+    let tunnel_arg = || Arg::new("tunnel").required(true).help("Tunnel name");
+
+    Command::new("ctl")
+        .about("Control a running stun daemon")
+        .subcommand_required(true)
+        .arg(socket_arg())
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(clap::ArgAction::SetTrue)
+                .help("Print the raw JSON response"),
+        )
+        .subcommand(Command::new("status").about("Show detailed tunnel status"))
+        .subcommand(Command::new("list").about("List tunnel names"))
+        .subcommand(
+            Command::new("restart")
+                .about("Restart a tunnel")
+                .arg(tunnel_arg()),
+        )
+        .subcommand(
+            Command::new("add").about("Add a tunnel").arg(
+                Arg::new("spec")
+                    .required(true)
+                    .help("Forwarding spec, e.g. 8080:127.0.0.1:80"),
+            ),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove a tunnel")
+                .arg(tunnel_arg()),
+        )
+        .subcommand(
+            Command::new("pause")
+                .about("Pause a tunnel")
+                .arg(tunnel_arg()),
+        )
+        .subcommand(
+            Command::new("resume")
+                .about("Resume a paused tunnel")
+                .arg(tunnel_arg()),
+        )
+        .subcommand(Command::new("reload").about("Re-read the daemon configuration file"))
+        .subcommand(Command::new("shutdown").about("Stop the daemon"))
+}
+
+/// Run tunnels and serve the control socket until shutdown is requested
+#[cfg(unix)]
+async fn run_daemon(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    use stun::control::ControlServer;
+
+    let (config_source, config) = load_config(matches)?;
+
+    let mut manager = TunnelManager::new(config.clone())?;
+    let handle = manager.start_background().await?;
+    let manager = Arc::new(manager);
+    spawn_watcher(matches, config_source.clone(), config, &manager);
+
+    let server = ControlServer::bind(
+        socket_path(matches),
+        Arc::clone(&manager),
+        Some(config_source),
+    )?;
+    info!("Daemon started. Use `stun ctl` to manage it.");
+
+    tokio::select! {
+        _ = server.run() => {}
+        _ = signal::ctrl_c() => {
+            info!("Received Ctrl+C, shutting down...");
+        }
+    }
+
+    manager.stop().await?;
+    if let Err(e) = handle.await {
+        error!("Manager task join error: {}", e);
+    }
+
+    info!("Shutdown complete");
+    Ok(())
+}
+
+/// Send one request to the daemon and print the response
+#[cfg(unix)]
+async fn run_ctl(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    use stun::control::{ControlRequest, ControlResponse, send_request};
+
+    let tunnel = |sub: &ArgMatches| {
+        sub.get_one::<String>("tunnel")
+            .expect("tunnel argument is required")
+            .clone()
+    };
+
+    let request = match matches.subcommand() {
+        Some(("status", _)) => ControlRequest::Status,
+        Some(("list", _)) => ControlRequest::List,
+        Some(("restart", sub)) => ControlRequest::Restart {
+            tunnel: tunnel(sub),
+        },
+        Some(("add", sub)) => ControlRequest::Add {
+            spec: sub
+                .get_one::<String>("spec")
+                .expect("spec argument is required")
+                .clone(),
+        },
+        Some(("remove", sub)) => ControlRequest::Remove {
+            tunnel: tunnel(sub),
+        },
+        Some(("pause", sub)) => ControlRequest::Pause {
+            tunnel: tunnel(sub),
+        },
+        Some(("resume", sub)) => ControlRequest::Resume {
+            tunnel: tunnel(sub),
+        },
+        Some(("reload", _)) => ControlRequest::Reload,
+        Some(("shutdown", _)) => ControlRequest::Shutdown,
+        _ => unreachable!("clap enforces a ctl subcommand"),
+    };
+
+    let response = send_request(socket_path(matches), &request).await?;
+
+    if matches.get_flag("json") {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+        return Ok(());
+    }
+
+    match response {
+        ControlResponse::Ok => println!("ok"),
+        ControlResponse::Status { tunnels } => {
+            println!(
+                "{:<36} {:<12} {:<8} {:>8} {:>8} {:>8}  LAST ERROR",
+                "NAME", "REMOTE", "HEALTH", "PID", "UPTIME", "RESTARTS"
+            );
+            // Tunnels expanded from a port range are listed together under its name
+            let first_of_group = |group: &Option<String>, index: usize| {
+                tunnels
+                    .iter()
+                    .position(|status| group.is_some() && status.group == *group)
+                    .unwrap_or(index)
+            };
+            let mut order: Vec<usize> = (0..tunnels.len()).collect();
+            order.sort_by_key(|&index| first_of_group(&tunnels[index].group, index));
+
+            let mut current_group = None;
+            for status in order.into_iter().map(|index| &tunnels[index]) {
+                if status.group.is_some() && status.group != current_group {
+                    println!("{}", status.group.as_deref().unwrap_or_default());
+                }
+                current_group = status.group.clone();
+                let indent = if status.group.is_some() { "  " } else { "" };
+                // Automatic bind ports show the port that was picked
+                let assigned = status
+                    .assigned_port
+                    .map(|port| format!(" [{port}]"))
+                    .unwrap_or_default();
+                println!(
+                    "{:<36} {:<12} {:<8} {:>8} {:>8} {:>8}  {}",
+                    format!("{indent}{}{assigned}", status.name),
+                    status.remote,
+                    format!("{:?}", status.health).to_lowercase(),
+                    status.pid.map(|p| p.to_string()).unwrap_or_default(),
+                    status
+                        .uptime_secs
+                        .map(|s| format!("{s}s"))
+                        .unwrap_or_default(),
+                    status.restart_count,
+                    status.last_error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        ControlResponse::List { tunnels } => {
+            for name in tunnels {
+                println!("{name}");
+            }
+        }
+        ControlResponse::Added { tunnel } => println!("added {tunnel}"),
+        ControlResponse::Reloaded { summary } => println!(
+            "reloaded: {} added, {} removed, {} restarted",
+            summary.added.len(),
+            summary.removed.len(),
+            summary.restarted.len()
+        ),
+        ControlResponse::Error { message } => return Err(message.into()),
+    }
+
+    Ok(())
+}
@@ -1,44 +1,191 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
+use serde::{Deserialize, Serialize};
 use tokio::{
-    process::Child,
-    sync::{RwLock, mpsc},
+    sync::{Mutex, RwLock, broadcast, mpsc, oneshot},
     time::{Instant, interval, sleep},
 };
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    config::Config,
-    error::StunResult,
+    config::{Config, DEFAULT_REMOTE, Endpoint, FailoverPolicy, ForwardingMode, TunnelConfig},
+    error::{StunError, StunResult},
+    events::TunnelEvent,
     forwarding::ForwardingSpec,
     health::{HealthChecker, TunnelHealth},
-    ssh::SshClient,
+    ports::{self, free_port, socket_host},
+    status::TunnelStatus,
+    transport::{ForwardHandle, TunnelTransport, build_transport, build_transports},
 };
 
 /// A managed tunnel with its associated process and health status
 #[derive(Debug)]
 struct TunnelInfo {
-    /// The SSH process for this tunnel
-    process: Option<Child>,
+    /// The running forward (own SSH process or multiplexed) for this tunnel
+    process: Option<ForwardHandle>,
     /// Current health status
     health: TunnelHealth,
-    /// Forwarding specification
-    spec: ForwardingSpec,
+    /// Name, forwarding specification and per-tunnel options
+    tunnel: TunnelConfig,
     /// Number of consecutive health check failures
     failure_count: u32,
     /// Next allowed restart time (with backoff). None means restart allowed immediately
     next_restart_at: Option<Instant>,
     /// Current backoff duration in seconds
     backoff_secs: u64,
+    /// Paused tunnels are neither health checked nor restarted
+    paused: bool,
+    /// PID of the current SSH process
+    pid: Option<u32>,
+    /// Endpoint of the remote used by the latest start attempt
+    endpoint: Option<Endpoint>,
+    /// When the current SSH process was started
+    started_at: Option<Instant>,
+    /// Number of restarts (automatic or requested)
+    restart_count: u32,
+    /// Most recent launch or health check error
+    last_error: Option<String>,
+    /// Duration of the last successful health probe
+    last_probe_latency: Option<Duration>,
+    /// Port picked for an automatic bind port, kept across restarts
+    assigned_port: Option<u16>,
+    /// The current forward has not been seen healthy yet, so whether its start
+    /// succeeded is still open
+    unconfirmed_start: bool,
+}
+
+impl TunnelInfo {
+    fn new(tunnel: TunnelConfig, backoff_secs: u64) -> Self {
+        let paused = !tunnel.enabled;
+        Self {
+            process: None,
+            health: if paused {
+                TunnelHealth::Paused
+            } else {
+                TunnelHealth::Unknown
+            },
+            tunnel,
+            failure_count: 0,
+            next_restart_at: None,
+            backoff_secs,
+            paused,
+            pid: None,
+            endpoint: None,
+            started_at: None,
+            restart_count: 0,
+            last_error: None,
+            last_probe_latency: None,
+            assigned_port: None,
+            unconfirmed_start: false,
+        }
+    }
+
+    /// Tunnel definition with the assigned port in place of an automatic bind port
+    fn active_tunnel(&self) -> TunnelConfig {
+        let mut tunnel = self.tunnel.clone();
+        if let Some(port) = self.assigned_port
+            && tunnel.spec.is_auto_port()
+        {
+            tunnel.spec = tunnel.spec.with_bind_port(port);
+        }
+        tunnel
+    }
+
+    /// Record a freshly opened forward
+    fn attach(&mut self, handle: ForwardHandle) {
+        self.pid = handle.pid();
+        self.started_at = Some(Instant::now());
+        self.process = Some(handle);
+        self.unconfirmed_start = true;
+    }
+
+    /// Forget the current forward, returning it so the caller can close it
+    fn detach(&mut self) -> Option<ForwardHandle> {
+        self.pid = None;
+        self.started_at = None;
+        self.unconfirmed_start = false;
+        self.process.take()
+    }
+
+    fn status(&self, name: &str) -> TunnelStatus {
+        let now = Instant::now();
+        TunnelStatus {
+            name: name.to_string(),
+            spec: self.tunnel.spec.clone(),
+            remote: self.tunnel.remote.clone(),
+            endpoint: self.endpoint.as_ref().map(Endpoint::to_string),
+            health: self.health.clone(),
+            pid: self.pid,
+            uptime_secs: self.started_at.map(|at| now.duration_since(at).as_secs()),
+            restart_count: self.restart_count,
+            consecutive_failures: self.failure_count,
+            last_error: self.last_error.clone(),
+            next_restart_in_secs: self
+                .next_restart_at
+                .map(|at| at.saturating_duration_since(now).as_secs()),
+            last_probe_latency_ms: self
+                .last_probe_latency
+                .map(|latency| latency.as_millis() as u64),
+            tags: self.tunnel.tags.clone(),
+            group: self.tunnel.group.clone(),
+            assigned_port: self.assigned_port,
+        }
+    }
+}
+
+/// Runtime control requests handled by the management loop
+enum ControlCommand {
+    Add {
+        tunnel: Box<TunnelConfig>,
+        reply: oneshot::Sender<StunResult<String>>,
+    },
+    Remove {
+        key: String,
+        reply: oneshot::Sender<StunResult<()>>,
+    },
+    Restart {
+        key: String,
+        reply: oneshot::Sender<StunResult<()>>,
+    },
+    Pause {
+        key: String,
+        reply: oneshot::Sender<StunResult<()>>,
+    },
+    Resume {
+        key: String,
+        reply: oneshot::Sender<StunResult<()>>,
+    },
+    Reload {
+        config: Box<Config>,
+        reply: oneshot::Sender<StunResult<ReloadSummary>>,
+    },
+}
+
+/// Tunnels touched by a configuration reload
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReloadSummary {
+    /// Tunnels that were not in the previous configuration
+    pub added: Vec<String>,
+    /// Tunnels that are no longer configured
+    pub removed: Vec<String>,
+    /// Tunnels relaunched because their definition or connection settings changed
+    pub restarted: Vec<String>,
 }
 
 /// Main tunnel manager that handles multiple SSH port forwarding connections
 pub struct TunnelManager {
     config: Config,
-    ssh_client: SshClient,
+    transports: HashMap<String, Arc<dyn TunnelTransport>>,
     health_checker: HealthChecker,
     tunnels: Arc<RwLock<HashMap<String, TunnelInfo>>>,
     shutdown_tx: Option<mpsc::Sender<()>>,
+    command_tx: Option<mpsc::Sender<ControlCommand>>,
+    events_tx: broadcast::Sender<TunnelEvent>,
     health_check_interval: Duration,
     max_failures: u32,
     backoff_base_secs: u64,
@@ -49,19 +196,38 @@ impl TunnelManager {
     /// Create a new tunnel manager with the given configuration
     pub fn new(config: Config) -> StunResult<Self> {
         config.validate()?;
+        let transports = build_transports(&config)?;
+        Self::with_transports(config, transports)
+    }
+
+    /// Create a tunnel manager that opens the top-level remote's forwards through a
+    /// custom transport. A reload that changes that remote replaces it with the
+    /// configured transport.
+    pub fn with_transport(config: Config, transport: Arc<dyn TunnelTransport>) -> StunResult<Self> {
+        config.validate()?;
+        let mut transports = build_transports(&config)?;
+        transports.insert(DEFAULT_REMOTE.to_string(), transport);
+        Self::with_transports(config, transports)
+    }
 
+    fn with_transports(
+        config: Config,
+        transports: HashMap<String, Arc<dyn TunnelTransport>>,
+    ) -> StunResult<Self> {
         let timeout = config.timeout.unwrap_or(2);
         let backoff_base = config.backoff_base_secs.unwrap_or(1);
         let backoff_max = config.backoff_max_secs.unwrap_or(30);
-        let ssh_client = SshClient::new(config.clone());
         let health_checker = HealthChecker::new(timeout);
+        let (events_tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
 
         Ok(Self {
             config,
-            ssh_client,
+            transports,
             health_checker,
             tunnels: Arc::new(RwLock::new(HashMap::new())),
             shutdown_tx: None,
+            command_tx: None,
+            events_tx,
             health_check_interval: Duration::from_secs(5), // Health check every 5 seconds
             max_failures: 3, // Max consecutive failures before scheduling restart
             backoff_base_secs: backoff_base,
@@ -71,62 +237,7 @@ impl TunnelManager {
 
     /// Start the tunnel manager
     pub async fn start(&mut self) -> StunResult<()> {
-        info!("Starting tunnel manager");
-
-        // Parse forwarding specifications
-        let mut specs = Vec::new();
-        for spec_str in &self.config.forwarding_list {
-            let spec = ForwardingSpec::parse(spec_str)?;
-            specs.push(spec);
-        }
-
-        // Initialize tunnels
-        {
-            let mut tunnels = self.tunnels.write().await;
-            for spec in specs {
-                let key = spec.to_ssh_arg();
-                tunnels.insert(
-                    key,
-                    TunnelInfo {
-                        process: None,
-                        health: TunnelHealth::Unknown,
-                        spec,
-                        failure_count: 0,
-                        next_restart_at: None,
-                        backoff_secs: self.backoff_base_secs,
-                    },
-                );
-            }
-        }
-
-        // Start all tunnels initially
-        self.start_all_tunnels().await?;
-
-        // Start health checking and management loop
-        let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
-        self.shutdown_tx = Some(shutdown_tx);
-
-        let tunnels = Arc::clone(&self.tunnels);
-        let ssh_client = SshClient::new(self.config.clone());
-        let health_checker = self.health_checker.clone();
-        let health_check_interval = self.health_check_interval;
-        let max_failures = self.max_failures;
-        let backoff_max_secs = self.backoff_max_secs;
-
-        let management_task = tokio::spawn(async move {
-            Self::management_loop(
-                tunnels,
-                ssh_client,
-                health_checker,
-                health_check_interval,
-                max_failures,
-                backoff_max_secs,
-                shutdown_rx,
-            )
-            .await;
-        });
-
-        info!("Tunnel manager started successfully");
+        let management_task = self.start_background().await?;
 
         // For CLI usage, wait for the management task
         if let Err(e) = management_task.await {
@@ -139,191 +250,817 @@ impl TunnelManager {
     /// Start the tunnel manager but return immediately with the management task handle.
     /// Use stop() to trigger shutdown and then await the returned handle to finish.
     pub async fn start_background(&mut self) -> StunResult<tokio::task::JoinHandle<()>> {
-        info!("Starting tunnel manager (background)");
+        info!("Starting tunnel manager");
 
-        // Parse forwarding specifications
-        let mut specs = Vec::new();
-        for spec_str in &self.config.forwarding_list {
-            let spec = ForwardingSpec::parse(spec_str)?;
-            specs.push(spec);
-        }
+        // Resolve forwarding entries
+        let configured = self.config.tunnels()?;
 
         // Initialize tunnels
         {
             let mut tunnels = self.tunnels.write().await;
-            for spec in specs {
-                let key = spec.to_ssh_arg();
-                tunnels.insert(
-                    key,
-                    TunnelInfo {
-                        process: None,
-                        health: TunnelHealth::Unknown,
-                        spec,
-                        failure_count: 0,
-                        next_restart_at: None,
-                        backoff_secs: self.backoff_base_secs,
-                    },
-                );
+            for tunnel in configured {
+                let key = tunnel.name.clone();
+                tunnels.insert(key, TunnelInfo::new(tunnel, self.backoff_base_secs));
             }
         }
 
+        let management_loop = ManagementLoop {
+            config: self.config.clone(),
+            tunnels: Arc::clone(&self.tunnels),
+            transports: self.transports.clone(),
+            health_checker: self.health_checker.clone(),
+            events_tx: self.events_tx.clone(),
+            endpoints: Mutex::new(HashMap::new()),
+            health_check_interval: self.health_check_interval,
+            max_failures: self.max_failures,
+            backoff_base_secs: self.backoff_base_secs,
+            backoff_max_secs: self.backoff_max_secs,
+        };
+
         // Start all tunnels initially
-        self.start_all_tunnels().await?;
+        management_loop.start_all_tunnels().await;
 
         // Start health checking and management loop
         let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
         self.shutdown_tx = Some(shutdown_tx);
-
-        let tunnels = Arc::clone(&self.tunnels);
-        let ssh_client = SshClient::new(self.config.clone());
-        let health_checker = self.health_checker.clone();
-        let health_check_interval = self.health_check_interval;
-        let max_failures = self.max_failures;
-        let backoff_max_secs = self.backoff_max_secs;
+        let (command_tx, command_rx) = mpsc::channel(16);
+        self.command_tx = Some(command_tx);
 
         let management_task = tokio::spawn(async move {
-            Self::management_loop(
-                tunnels,
-                ssh_client,
-                health_checker,
-                health_check_interval,
-                max_failures,
-                backoff_max_secs,
-                shutdown_rx,
-            )
-            .await;
+            management_loop.run(shutdown_rx, command_rx).await;
         });
 
-        info!("Tunnel manager started successfully (background)");
+        info!("Tunnel manager started successfully");
         Ok(management_task)
     }
 
     /// Stop the tunnel manager and all tunnels
-    pub async fn stop(&mut self) -> StunResult<()> {
+    pub async fn stop(&self) -> StunResult<()> {
         info!("Stopping tunnel manager");
 
-        // Send shutdown signal
-        if let Some(tx) = self.shutdown_tx.take() {
+        // The management loop stops all tunnels before it exits;
+        // sending fails harmlessly if the loop already exited
+        if let Some(tx) = &self.shutdown_tx {
             let _ = tx.send(()).await;
         }
 
-        // Stop all tunnels
-        self.stop_all_tunnels().await?;
-
         info!("Tunnel manager stopped");
         Ok(())
     }
 
-    /// Start all configured tunnels
-    async fn start_all_tunnels(&self) -> StunResult<()> {
-        // Snapshot which tunnels need to be started without holding the lock across awaits
-        let to_start: Vec<(String, ForwardingSpec)> = {
+    /// Whether the management loop is running and accepts control commands
+    pub fn is_running(&self) -> bool {
+        self.command_tx.as_ref().is_some_and(|tx| !tx.is_closed())
+    }
+
+    /// Subscribe to tunnel lifecycle events.
+    ///
+    /// Receivers that fall more than the channel capacity behind observe
+    /// `RecvError::Lagged` and skip the oldest events.
+    pub fn subscribe(&self) -> broadcast::Receiver<TunnelEvent> {
+        self.events_tx.subscribe()
+    }
+
+    /// Add and start a new tunnel while the manager is running.
+    /// Returns the key under which the tunnel is tracked.
+    pub async fn add_tunnel(&self, spec: ForwardingSpec) -> StunResult<String> {
+        self.add_tunnel_config(TunnelConfig::from_spec(spec)).await
+    }
+
+    /// Add a tunnel with a name and per-tunnel options while the manager is running.
+    /// Disabled tunnels are added paused. Returns the tunnel's name.
+    pub async fn add_tunnel_config(&self, tunnel: TunnelConfig) -> StunResult<String> {
+        let tunnel = Box::new(tunnel);
+        self.send_command(|reply| ControlCommand::Add { tunnel, reply })
+            .await
+    }
+
+    /// Stop a running tunnel and stop tracking it
+    pub async fn remove_tunnel(&self, key: &str) -> StunResult<()> {
+        let key = key.to_string();
+        self.send_command(|reply| ControlCommand::Remove { key, reply })
+            .await
+    }
+
+    /// Kill and relaunch a single tunnel, resetting its failure and backoff state
+    pub async fn restart_tunnel(&self, key: &str) -> StunResult<()> {
+        let key = key.to_string();
+        self.send_command(|reply| ControlCommand::Restart { key, reply })
+            .await
+    }
+
+    /// Stop a tunnel's SSH process without forgetting it; health checks skip it until resumed
+    pub async fn pause_tunnel(&self, key: &str) -> StunResult<()> {
+        let key = key.to_string();
+        self.send_command(|reply| ControlCommand::Pause { key, reply })
+            .await
+    }
+
+    /// Relaunch a previously paused tunnel
+    pub async fn resume_tunnel(&self, key: &str) -> StunResult<()> {
+        let key = key.to_string();
+        self.send_command(|reply| ControlCommand::Resume { key, reply })
+            .await
+    }
+
+    /// Apply a new configuration to the running manager.
+    ///
+    /// Tunnels missing from `config` are stopped, new ones are started and tunnels
+    /// whose entry changed are relaunched. Changing a remote restarts the tunnels
+    /// using it; changing the forwarding mode restarts every remaining tunnel.
+    pub async fn reload(&self, config: Config) -> StunResult<ReloadSummary> {
+        config.validate()?;
+        let config = Box::new(config);
+        self.send_command(|reply| ControlCommand::Reload { config, reply })
+            .await
+    }
+
+    /// Hand a control command to the management loop and wait for its reply
+    async fn send_command<T>(
+        &self,
+        build: impl FnOnce(oneshot::Sender<StunResult<T>>) -> ControlCommand,
+    ) -> StunResult<T> {
+        let tx = self
+            .command_tx
+            .as_ref()
+            .ok_or_else(|| StunError::Tunnel("Tunnel manager is not running".to_string()))?;
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(build(reply_tx))
+            .await
+            .map_err(|_| StunError::Tunnel("Management loop has stopped".to_string()))?;
+
+        reply_rx.await.map_err(|_| {
+            StunError::Tunnel("Management loop dropped the control request".to_string())
+        })?
+    }
+
+    /// Get the status of all tunnels, sorted by name
+    pub async fn get_status(&self) -> Vec<TunnelStatus> {
+        let tunnels = self.tunnels.read().await;
+        let mut statuses: Vec<TunnelStatus> =
+            tunnels.iter().map(|(key, info)| info.status(key)).collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Get the status of a single tunnel
+    pub async fn tunnel_status(&self, key: &str) -> Option<TunnelStatus> {
+        let tunnels = self.tunnels.read().await;
+        tunnels.get(key).map(|info| info.status(key))
+    }
+
+    /// Local address a tunnel listens on, including the port picked for an automatic
+    /// bind port once the tunnel has been launched
+    pub async fn local_addr(&self, key: &str) -> StunResult<SocketAddr> {
+        let tunnel = {
             let tunnels = self.tunnels.read().await;
             tunnels
-                .iter()
-                .filter_map(|(key, info)| {
-                    if info.process.is_none() {
-                        Some((key.clone(), info.spec.clone()))
-                    } else {
-                        None
-                    }
-                })
-                .collect()
+                .get(key)
+                .ok_or_else(|| unknown_tunnel(key))?
+                .active_tunnel()
         };
-
-        // Start them without holding the lock, then apply results
-        let mut results: Vec<(String, Result<Child, crate::error::StunError>)> = Vec::new();
-        for (key, spec) in to_start {
-            let res = self.ssh_client.start_forwarding(&spec).await;
-            results.push((key, res));
+        let spec = &tunnel.spec;
+        if spec.bind_socket.is_some() || spec.mode_or(self.config.mode) == ForwardingMode::Remote {
+            return Err(StunError::Tunnel(format!(
+                "Tunnel {key} does not listen on a local TCP port"
+            )));
+        }
+        if spec.is_auto_port() {
+            return Err(StunError::Tunnel(format!(
+                "Tunnel {key} has not been assigned a port yet"
+            )));
         }
 
-        // Apply results under a short write lock
-        let mut tunnels = self.tunnels.write().await;
-        for (key, res) in results {
-            if let Some(info) = tunnels.get_mut(&key) {
-                if info.process.is_some() {
-                    continue; // already started elsewhere
+        let address = socket_host(spec.effective_bind_address());
+        (address, spec.bind_port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| StunError::Network(format!("Cannot resolve bind address {address}")))
+    }
+}
+
+/// State owned by the background management task.
+///
+/// Health checks and control commands are both processed by this task, so a
+/// control operation never races with a health check holding a tunnel's process.
+struct ManagementLoop {
+    config: Config,
+    tunnels: Arc<RwLock<HashMap<String, TunnelInfo>>>,
+    /// One transport per remote, keyed by remote name
+    transports: HashMap<String, Arc<dyn TunnelTransport>>,
+    /// Active endpoint and failed starts per remote; remotes without an entry use the primary
+    endpoints: Mutex<HashMap<String, EndpointState>>,
+    health_checker: HealthChecker,
+    events_tx: broadcast::Sender<TunnelEvent>,
+    health_check_interval: Duration,
+    max_failures: u32,
+    backoff_base_secs: u64,
+    backoff_max_secs: u64,
+}
+
+impl ManagementLoop {
+    /// Publish an event; having no subscribers is not an error
+    fn emit(&self, event: TunnelEvent) {
+        let _ = self.events_tx.send(event);
+    }
+
+    /// Main management loop that runs health checks and restarts failed tunnels
+    async fn run(
+        mut self,
+        mut shutdown_rx: mpsc::Receiver<()>,
+        mut command_rx: mpsc::Receiver<ControlCommand>,
+    ) {
+        let mut interval = interval(self.health_check_interval);
+        interval.tick().await; // Skip first tick
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    self.perform_health_checks().await;
+                    self.fail_over().await;
                 }
-                match res {
-                    Ok(process) => {
-                        info!("Started tunnel: {}", key);
-                        info.process = Some(process);
-                        info.health = TunnelHealth::Unknown;
-                        info.failure_count = 0;
-                    }
-                    Err(e) => {
-                        error!("Failed to start tunnel {}: {}", key, e);
-                        info.health = TunnelHealth::Down;
-                    }
+                Some(command) = command_rx.recv() => {
+                    self.handle_command(command).await;
+                }
+                _ = shutdown_rx.recv() => {
+                    debug!("Received shutdown signal in management loop");
+                    break;
                 }
             }
         }
 
-        Ok(())
+        self.stop_all_tunnels().await;
+        for transport in self.transports.values() {
+            transport.shutdown().await;
+        }
     }
 
-    /// Stop all tunnels
-    async fn stop_all_tunnels(&self) -> StunResult<()> {
-        // Take out all processes under a short lock
-        let to_stop: Vec<(String, Option<Child>)> = {
+    /// Transport carrying the tunnels of `remote`
+    fn transport(&self, remote: &str) -> StunResult<&Arc<dyn TunnelTransport>> {
+        self.transports
+            .get(remote)
+            .ok_or_else(|| StunError::Tunnel(format!("Unknown remote: {remote}")))
+    }
+
+    /// Stop every running forward
+    async fn stop_all_tunnels(&self) {
+        // Take out all handles under a short lock
+        let to_stop: Vec<(String, TunnelConfig, Option<ForwardHandle>)> = {
             let mut tunnels = self.tunnels.write().await;
             tunnels
                 .iter_mut()
-                .map(|(key, info)| (key.clone(), info.process.take()))
+                .map(|(key, info)| (key.clone(), info.active_tunnel(), info.detach()))
                 .collect()
         };
 
         // Stop outside of the lock
-        for (key, process_opt) in to_stop {
-            if let Some(process) = process_opt {
+        for (key, tunnel, handle) in to_stop {
+            if handle.is_some() {
                 info!("Stopping tunnel: {}", key);
-                if let Err(e) = SshClient::kill_process(process).await {
-                    warn!("Error stopping tunnel {}: {}", key, e);
+            }
+            self.close(&key, &tunnel, handle).await;
+        }
+    }
+
+    /// Launch every tunnel that has no running forward yet
+    async fn start_all_tunnels(&self) {
+        let to_start: Vec<(String, TunnelConfig)> = {
+            let tunnels = self.tunnels.read().await;
+            tunnels
+                .iter()
+                .filter(|(_, info)| info.process.is_none() && !info.paused)
+                .map(|(key, info)| (key.clone(), info.tunnel.clone()))
+                .collect()
+        };
+
+        for (key, tunnel) in to_start {
+            self.launch(&key, &tunnel, false).await;
+        }
+    }
+
+    /// Close a forward on its remote's transport, logging instead of failing
+    async fn close(&self, key: &str, tunnel: &TunnelConfig, handle: Option<ForwardHandle>) {
+        let Some(handle) = handle else {
+            return;
+        };
+        let result = match self.transport(&tunnel.remote) {
+            Ok(transport) => transport.close(&tunnel.spec, handle).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("Error stopping tunnel {}: {}", key, e);
+        }
+    }
+
+    /// Apply a single control command and send back its result
+    async fn handle_command(&mut self, command: ControlCommand) {
+        match command {
+            ControlCommand::Add { tunnel, reply } => {
+                let _ = reply.send(self.add_tunnel(*tunnel).await);
+            }
+            ControlCommand::Remove { key, reply } => {
+                let _ = reply.send(self.remove_tunnel(&key).await);
+            }
+            ControlCommand::Restart { key, reply } => {
+                let _ = reply.send(self.restart_tunnel(&key).await);
+            }
+            ControlCommand::Pause { key, reply } => {
+                let _ = reply.send(self.pause_tunnel(&key).await);
+            }
+            ControlCommand::Resume { key, reply } => {
+                let _ = reply.send(self.resume_tunnel(&key).await);
+            }
+            ControlCommand::Reload { config, reply } => {
+                let _ = reply.send(self.reload(*config).await);
+            }
+        }
+    }
+
+    /// Reconcile the tunnel map with a new configuration
+    async fn reload(&mut self, config: Config) -> StunResult<ReloadSummary> {
+        let mut desired: HashMap<String, TunnelConfig> = config
+            .tunnels()?
+            .into_iter()
+            .map(|tunnel| (tunnel.name.clone(), tunnel))
+            .collect();
+
+        // `tunnels` has resolved every mode; check the remotes too before anything
+        // changes, so a bad tunnel leaves the running configuration untouched
+        if let Some(tunnel) = desired
+            .values()
+            .find(|tunnel| config.remote_config(&tunnel.remote).is_none())
+        {
+            return Err(StunError::Tunnel(format!(
+                "Unknown remote: {}",
+                tunnel.remote
+            )));
+        }
+
+        // Remotes whose connection settings changed get a fresh transport;
+        // a mode change affects all of them
+        let mode_changed = config.mode != self.config.mode;
+        let mut replaced = HashMap::new();
+        for name in config.remote_names() {
+            if mode_changed
+                || config.remote_config(&name) != self.config.remote_config(&name)
+                || !self.transports.contains_key(&name)
+            {
+                replaced.insert(name.clone(), build_transport(&config.for_remote(&name)?)?);
+            }
+        }
+        self.health_checker = HealthChecker::new(config.timeout.unwrap_or(2));
+        self.backoff_base_secs = config.backoff_base_secs.unwrap_or(1);
+        self.backoff_max_secs = config.backoff_max_secs.unwrap_or(30);
+        self.config = config;
+
+        let current: Vec<String> = self.tunnels.read().await.keys().cloned().collect();
+        let mut summary = ReloadSummary::default();
+
+        // Tunnels whose entry changed or whose remote is replaced are closed with
+        // their old definition on the old transport
+        let mut stale = Vec::new();
+        for key in current {
+            match desired.remove(&key) {
+                None => {
+                    self.remove_tunnel(&key).await?;
+                    summary.removed.push(key);
+                }
+                Some(tunnel) => {
+                    let mut map = self.tunnels.write().await;
+                    let Some(info) = map.get_mut(&key) else {
+                        continue;
+                    };
+                    let old = if info.tunnel != tunnel {
+                        let old = info.active_tunnel();
+                        info.tunnel = tunnel;
+                        info.assigned_port = None;
+                        info.paused = !info.tunnel.enabled;
+                        if info.paused {
+                            info.health = TunnelHealth::Paused;
+                        }
+                        old
+                    } else if !info.paused && replaced.contains_key(&info.tunnel.remote) {
+                        info.active_tunnel()
+                    } else {
+                        continue;
+                    };
+                    stale.push((key, old, info.detach(), info.paused));
                 }
             }
         }
 
+        let mut relaunch = Vec::with_capacity(stale.len());
+        for (key, old, handle, paused) in stale {
+            self.close(&key, &old, handle).await;
+            if !paused {
+                relaunch.push(key.clone());
+            }
+            summary.restarted.push(key);
+        }
+
+        // Swap in the new transports once nothing uses the old ones
+        let retired: Vec<String> = self
+            .transports
+            .keys()
+            .filter(|name| self.config.remote_config(name).is_none())
+            .cloned()
+            .collect();
+        let mut endpoints = self.endpoints.lock().await;
+        for name in retired {
+            endpoints.remove(&name);
+            if let Some(transport) = self.transports.remove(&name) {
+                transport.shutdown().await;
+            }
+        }
+        // New transports connect to the primary endpoint
+        for (name, transport) in replaced {
+            endpoints.remove(&name);
+            if let Some(old) = self.transports.insert(name, transport) {
+                old.shutdown().await;
+            }
+        }
+        drop(endpoints);
+
+        for key in relaunch {
+            let tunnel = self
+                .tunnels
+                .read()
+                .await
+                .get(&key)
+                .map(|info| info.tunnel.clone());
+            if let Some(tunnel) = tunnel {
+                self.launch(&key, &tunnel, true).await;
+            }
+        }
+        for tunnel in desired.into_values() {
+            summary.added.push(self.add_tunnel(tunnel).await?);
+        }
+
+        summary.added.sort();
+        summary.removed.sort();
+        summary.restarted.sort();
+        info!(
+            "Reloaded configuration: {} added, {} removed, {} restarted",
+            summary.added.len(),
+            summary.removed.len(),
+            summary.restarted.len()
+        );
+        Ok(summary)
+    }
+
+    async fn add_tunnel(&self, tunnel: TunnelConfig) -> StunResult<String> {
+        let key = tunnel.name.clone();
+        tunnel.spec.resolve_mode(self.config.mode)?;
+        self.transport(&tunnel.remote)?;
+        {
+            let mut map = self.tunnels.write().await;
+            if map.contains_key(&key) {
+                return Err(StunError::Tunnel(format!("Tunnel {key} already exists")));
+            }
+            map.insert(
+                key.clone(),
+                TunnelInfo::new(tunnel.clone(), self.backoff_base_secs),
+            );
+        }
+
+        info!("Added tunnel: {}", key);
+        if tunnel.enabled {
+            self.launch(&key, &tunnel, false).await;
+        }
+        Ok(key)
+    }
+
+    async fn remove_tunnel(&self, key: &str) -> StunResult<()> {
+        let info = self
+            .tunnels
+            .write()
+            .await
+            .remove(key)
+            .ok_or_else(|| unknown_tunnel(key))?;
+
+        self.close(key, &info.active_tunnel(), info.process).await;
+
+        info!("Removed tunnel: {}", key);
+        self.emit(TunnelEvent::Removed {
+            tunnel: key.to_string(),
+        });
         Ok(())
     }
 
-    /// Main management loop that runs health checks and restarts failed tunnels
-    async fn management_loop(
-        tunnels: Arc<RwLock<HashMap<String, TunnelInfo>>>,
-        ssh_client: SshClient,
-        health_checker: HealthChecker,
-        health_check_interval: Duration,
-        max_failures: u32,
-        backoff_max_secs: u64,
-        mut shutdown_rx: mpsc::Receiver<()>,
-    ) {
-        let mut interval = interval(health_check_interval);
-        interval.tick().await; // Skip first tick
+    async fn restart_tunnel(&self, key: &str) -> StunResult<()> {
+        let (process_opt, tunnel) = {
+            let mut map = self.tunnels.write().await;
+            let info = map.get_mut(key).ok_or_else(|| unknown_tunnel(key))?;
+            info.paused = false;
+            (info.detach(), info.active_tunnel())
+        };
 
-        loop {
-            tokio::select! {
-                _ = interval.tick() => {
-                    // Local mode allows local TCP probing; remote mode should not attempt local TCP checks
-                    let is_local_mode = ssh_client.is_local_mode();
-                    Self::perform_health_checks(&tunnels, &ssh_client, &health_checker, max_failures, backoff_max_secs, is_local_mode).await;
-                }
-                _ = shutdown_rx.recv() => {
-                    debug!("Received shutdown signal in management loop");
-                    break;
+        self.close(key, &tunnel, process_opt).await;
+
+        info!("Restarting tunnel on request: {}", key);
+        self.launch(key, &tunnel, true).await;
+        Ok(())
+    }
+
+    async fn pause_tunnel(&self, key: &str) -> StunResult<()> {
+        let (process_opt, tunnel) = {
+            let mut map = self.tunnels.write().await;
+            let info = map.get_mut(key).ok_or_else(|| unknown_tunnel(key))?;
+            info.paused = true;
+            info.health = TunnelHealth::Paused;
+            info.failure_count = 0;
+            info.next_restart_at = None;
+            (info.detach(), info.active_tunnel())
+        };
+
+        self.close(key, &tunnel, process_opt).await;
+
+        info!("Paused tunnel: {}", key);
+        self.emit(TunnelEvent::Paused {
+            tunnel: key.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn resume_tunnel(&self, key: &str) -> StunResult<()> {
+        let tunnel = {
+            let mut map = self.tunnels.write().await;
+            let info = map.get_mut(key).ok_or_else(|| unknown_tunnel(key))?;
+            if !info.paused {
+                return Err(StunError::Tunnel(format!("Tunnel {key} is not paused")));
+            }
+            info.paused = false;
+            info.health = TunnelHealth::Unknown;
+            info.tunnel.clone()
+        };
+
+        info!("Resuming tunnel: {}", key);
+        self.launch(key, &tunnel, false).await;
+        Ok(())
+    }
+
+    /// Start the SSH process for a tunnel and record it with fresh failure/backoff state.
+    /// `restart` selects between Started/Down and Restarted/RestartFailed events.
+    async fn launch(&self, key: &str, tunnel: &TunnelConfig, restart: bool) {
+        let result = async {
+            let transport = self.transport(&tunnel.remote)?;
+            let spec = self.bind_spec(key, tunnel).await?;
+            self.open_forward(transport, &spec).await
+        }
+        .await;
+        self.record_open_error(&tunnel.remote, &result).await;
+        let endpoint = self.active_endpoint(&tunnel.remote).await;
+
+        let mut map = self.tunnels.write().await;
+        let Some(info) = map.get_mut(key) else {
+            return;
+        };
+        info.failure_count = 0;
+        info.next_restart_at = None;
+        info.backoff_secs = self.backoff_base_secs;
+        info.endpoint = endpoint;
+        match result {
+            Ok(process) => {
+                info!("Started tunnel: {}", key);
+                if restart {
+                    info.restart_count += 1;
                 }
+                info.attach(process);
+                info.health = TunnelHealth::Unknown;
+                let tunnel = key.to_string();
+                self.emit(if restart {
+                    TunnelEvent::Restarted { tunnel }
+                } else {
+                    TunnelEvent::Started { tunnel }
+                });
+            }
+            Err(e @ StunError::PortInUse { .. }) => {
+                error!("Tunnel {} cannot start: {}", key, e);
+                info.health = TunnelHealth::Failed;
+                info.last_error = Some(e.to_string());
+                self.emit(TunnelEvent::Failed {
+                    tunnel: key.to_string(),
+                    error: e.to_string(),
+                });
+            }
+            Err(e) => {
+                error!("Failed to start tunnel {}: {}", key, e);
+                info.health = TunnelHealth::Down;
+                info.last_error = Some(e.to_string());
+                let tunnel = key.to_string();
+                self.emit(if restart {
+                    TunnelEvent::RestartFailed {
+                        tunnel,
+                        error: e.to_string(),
+                    }
+                } else {
+                    TunnelEvent::Down { tunnel }
+                });
+            }
+        }
+    }
+
+    /// Open a forward, first checking that a local bind port or socket is free. A taken
+    /// one fails with [`StunError::PortInUse`] instead of an ssh exit that retrying
+    /// cannot fix, or ssh replacing a socket another process still serves.
+    async fn open_forward(
+        &self,
+        transport: &Arc<dyn TunnelTransport>,
+        spec: &ForwardingSpec,
+    ) -> StunResult<ForwardHandle> {
+        let listens_locally = matches!(
+            spec.mode_or(self.config.mode),
+            ForwardingMode::Local | ForwardingMode::Dynamic
+        );
+        if listens_locally {
+            match &spec.bind_socket {
+                Some(path) => ports::check_socket_available(path)?,
+                None => ports::check_available(spec.effective_bind_address(), spec.bind_port)?,
+            }
+        }
+        transport.open(spec).await
+    }
+
+    /// Spec to open for a tunnel. An automatic bind port gets a free port on the first
+    /// launch, which later launches reuse so clients can keep connecting to it.
+    async fn bind_spec(&self, key: &str, tunnel: &TunnelConfig) -> StunResult<ForwardingSpec> {
+        if !tunnel.spec.is_auto_port() {
+            return Ok(tunnel.spec.clone());
+        }
+        let mut map = self.tunnels.write().await;
+        let info = map.get_mut(key).ok_or_else(|| unknown_tunnel(key))?;
+        let port = match info.assigned_port {
+            Some(port) => port,
+            None => {
+                let port = free_port(tunnel.spec.effective_bind_address())?;
+                info!("Assigned port {} to tunnel {}", port, key);
+                info.assigned_port = Some(port);
+                port
+            }
+        };
+        Ok(tunnel.spec.with_bind_port(port))
+    }
+
+    /// Count a forward that could not be opened as a failed start on `remote`. A port
+    /// taken locally says nothing about the remote, so it does not count.
+    async fn record_open_error(&self, remote: &str, result: &StunResult<ForwardHandle>) {
+        if let Err(e) = result
+            && !matches!(e, StunError::PortInUse { .. })
+        {
+            self.record_start(remote, false).await;
+        }
+    }
+
+    /// Count a forward start on `remote`; a success ends its streak of failed starts.
+    /// Opening a forward is not enough to succeed: the ssh process may still fail to
+    /// reach the remote, so a start is only settled by the tunnel's first health verdict.
+    async fn record_start(&self, remote: &str, ok: bool) {
+        let mut endpoints = self.endpoints.lock().await;
+        let state = endpoints.entry(remote.to_string()).or_default();
+        if ok {
+            state.failed_starts = 0;
+        } else {
+            state.failed_starts += 1;
+        }
+    }
+
+    /// Endpoint the remote's transport currently connects to
+    async fn active_endpoint(&self, remote: &str) -> Option<Endpoint> {
+        let active = self
+            .endpoints
+            .lock()
+            .await
+            .get(remote)
+            .map_or(0, |state| state.active);
+        self.config
+            .remote_config(remote)?
+            .endpoints()
+            .into_iter()
+            .nth(active)
+    }
+
+    /// Move remotes with fallbacks whose forwards keep failing to start to their next endpoint
+    async fn fail_over(&mut self) {
+        let due: Vec<(String, usize)> = {
+            let mut endpoints = self.endpoints.lock().await;
+            endpoints
+                .iter_mut()
+                .filter_map(|(remote, state)| {
+                    let remote_config = self.config.remote_config(remote)?;
+                    if remote_config.fallbacks.is_empty() {
+                        return None;
+                    }
+                    let threshold = match remote_config.failover {
+                        FailoverPolicy::Ordered => self.max_failures,
+                        FailoverPolicy::RoundRobin => 1,
+                    };
+                    if state.failed_starts < threshold {
+                        return None;
+                    }
+                    state.failed_starts = 0;
+                    state.active = (state.active + 1) % (remote_config.fallbacks.len() + 1);
+                    Some((remote.clone(), state.active))
+                })
+                .collect()
+        };
+
+        for (remote, active) in due {
+            if let Err(e) = self.switch_endpoint(&remote, active).await {
+                error!("Failed to switch endpoint of remote {}: {}", remote, e);
             }
         }
     }
 
+    /// Rebuild a remote's transport for another endpoint and move its forwards over
+    async fn switch_endpoint(&mut self, remote: &str, index: usize) -> StunResult<()> {
+        let mut config = self.config.for_remote(remote)?;
+        let endpoint = config.remote.endpoints().swap_remove(index);
+        config.remote = config.remote.at_endpoint(&endpoint);
+        let transport = build_transport(&config)?;
+        warn!("Remote {} failing over to {}", remote, endpoint);
+
+        let affected: Vec<(String, TunnelConfig, Option<ForwardHandle>)> = {
+            let mut map = self.tunnels.write().await;
+            map.iter_mut()
+                .filter(|(_, info)| info.tunnel.remote == remote && !info.paused)
+                .map(|(key, info)| (key.clone(), info.active_tunnel(), info.detach()))
+                .collect()
+        };
+        let mut relaunch = Vec::with_capacity(affected.len());
+        for (key, tunnel, handle) in affected {
+            self.close(&key, &tunnel, handle).await;
+            relaunch.push((key, tunnel));
+        }
+        if let Some(old) = self.transports.insert(remote.to_string(), transport) {
+            old.shutdown().await;
+        }
+        for (key, tunnel) in relaunch {
+            self.launch(&key, &tunnel, true).await;
+        }
+        Ok(())
+    }
+
+    /// Handle loss of a remote's shared connection: mark every tunnel carried
+    /// by it down, then try to reconnect and re-open all of its forwards.
+    async fn recover_connection(&self, remote: &str, transport: &Arc<dyn TunnelTransport>) {
+        let affected: Vec<(String, TunnelConfig, ForwardHandle)> = {
+            let mut map = self.tunnels.write().await;
+            map.iter_mut()
+                .filter(|(_, info)| {
+                    info.tunnel.remote == remote
+                        && info.process.as_ref().is_some_and(ForwardHandle::is_shared)
+                })
+                .filter_map(|(key, info)| {
+                    let handle = info.detach()?;
+                    info.health = TunnelHealth::Down;
+                    info.last_error = Some("SSH connection lost".to_string());
+                    Some((key.clone(), info.active_tunnel(), handle))
+                })
+                .collect()
+        };
+        if affected.is_empty() {
+            return;
+        }
+
+        warn!(
+            "SSH connection to remote {} lost, {} tunnels affected",
+            remote,
+            affected.len()
+        );
+        let mut relaunch = Vec::with_capacity(affected.len());
+        for (key, tunnel, handle) in affected {
+            self.emit(TunnelEvent::Down {
+                tunnel: key.clone(),
+            });
+            self.close(&key, &tunnel, Some(handle)).await;
+            relaunch.push((key, tunnel));
+        }
+
+        // On failure the tunnels stay down and follow the regular per-tunnel backoff
+        if let Err(e) = transport.reconnect().await {
+            error!("Failed to re-establish SSH connection to {}: {}", remote, e);
+            return;
+        }
+        for (key, tunnel) in relaunch {
+            self.launch(&key, &tunnel, true).await;
+        }
+    }
+
     /// Perform health checks on all tunnels and restart failed ones
-    async fn perform_health_checks(
-        tunnels: &Arc<RwLock<HashMap<String, TunnelInfo>>>,
-        ssh_client: &SshClient,
-        health_checker: &HealthChecker,
-        max_failures: u32,
-        backoff_max_secs: u64,
-        is_local_mode: bool,
-    ) {
+    async fn perform_health_checks(&self) {
+        let tunnels = &self.tunnels;
+        let health_checker = &self.health_checker;
+        let max_failures = self.max_failures;
+        let backoff_max_secs = self.backoff_max_secs;
+
+        // A dead shared connection takes every forward it carries down with it
+        for (remote, transport) in &self.transports {
+            if transport.connection_alive().await == Some(false) {
+                self.recover_connection(remote, transport).await;
+            }
+        }
+
         // Snapshot keys so we can process each tunnel without holding the lock
         let keys: Vec<String> = {
             let map = tunnels.read().await;
@@ -331,50 +1068,84 @@ impl TunnelManager {
         };
 
         for key in keys {
-            // Take process and clone spec under a short lock
+            // Take process and clone the tunnel definition under a short lock
             let (
                 mut process_opt,
-                spec,
+                tunnel,
                 mut failure_count,
                 prev_health,
                 mut next_restart_at,
                 mut backoff_secs,
+                unconfirmed_start,
             ) = {
                 let mut map = tunnels.write().await;
-                if let Some(info) = map.get_mut(&key) {
-                    (
-                        info.process.take(),
-                        info.spec.clone(),
-                        info.failure_count,
-                        info.health.clone(),
-                        info.next_restart_at,
-                        info.backoff_secs,
-                    )
-                } else {
-                    continue;
+                match map.get_mut(&key) {
+                    Some(info)
+                        if !info.paused
+                            && info.health != TunnelHealth::Failed
+                            && self.transports.contains_key(&info.tunnel.remote) =>
+                    {
+                        (
+                            info.process.take(),
+                            info.active_tunnel(),
+                            info.failure_count,
+                            info.health.clone(),
+                            info.next_restart_at,
+                            info.backoff_secs,
+                            info.unconfirmed_start,
+                        )
+                    }
+                    _ => continue,
                 }
             };
 
+            let transport = &self.transports[&tunnel.remote];
+            let spec = tunnel.spec;
+            let tunnel_checker = tunnel.timeout.map(HealthChecker::new);
+            let health_checker = tunnel_checker.as_ref().unwrap_or(health_checker);
+
+            // Local and dynamic forwards listen locally and allow local probing;
+            // remote forwards should not attempt local TCP checks
+            let is_local_mode = matches!(
+                spec.mode_or(self.config.mode),
+                ForwardingMode::Local | ForwardingMode::Dynamic
+            );
+
             // Check liveness without holding the lock
-            let process_alive = if let Some(ref mut process) = process_opt {
-                health_checker.check_ssh_process(process).await
-            } else {
-                false
+            let process_alive = match process_opt {
+                Some(ForwardHandle::Process(ref mut process)) => {
+                    health_checker.check_ssh_process(process).await
+                }
+                Some(ref mut handle) => transport.is_alive(handle).await,
+                None => false,
             };
 
             // Only perform local TCP probe for local mode
+            let mut probe_latency = None;
             let forwarding_healthy = if process_alive && is_local_mode {
                 // Give some time for port forwarding to become available
                 sleep(Duration::from_millis(500)).await;
-                health_checker.check_forwarding(&spec).await
+                let probe_started = Instant::now();
+                let reachable = if spec.is_dynamic() {
+                    health_checker
+                        .check_socks_proxy(&spec, tunnel.probe.clone())
+                        .await
+                } else {
+                    health_checker.check_forwarding(&spec).await
+                };
+                probe_latency = reachable.then(|| probe_started.elapsed());
+                reachable
             } else {
                 // For remote mode, optionally run a remote TCP probe if configured
                 if process_alive && !is_local_mode {
                     // Look up probe target by the exact spec string key
-                    let probe_target = ssh_client.remote_probe_target(&spec);
-                    if let Some((host, port)) = probe_target {
-                        match ssh_client.remote_tcp_probe(&host, port).await {
-                            Ok(true) => true,
+                    if let Some((host, port)) = &tunnel.probe {
+                        let probe_started = Instant::now();
+                        match transport.probe_remote(host, *port).await {
+                            Ok(true) => {
+                                probe_latency = Some(probe_started.elapsed());
+                                true
+                            }
                             Ok(false) => false,
                             Err(e) => {
                                 warn!("Remote probe failed: {}", e);
@@ -396,16 +1167,36 @@ impl TunnelManager {
                 process_alive
             };
 
+            let failure_reason = if !process_alive {
+                "SSH process is not running"
+            } else {
+                "Forwarded port is not reachable"
+            };
+
+            // The start succeeded once the tunnel is healthy, and failed if its process
+            // exited or it is about to be restarted without having been healthy
+            let start_failed = !is_healthy && (!process_alive || failure_count + 1 >= max_failures);
+            if unconfirmed_start && (is_healthy || start_failed) {
+                self.record_start(&tunnel.remote, is_healthy).await;
+                if let Some(info) = tunnels.write().await.get_mut(&key) {
+                    info.unconfirmed_start = false;
+                }
+            }
+
             // Apply updates and possible restarts with exponential backoff
             if is_healthy {
                 let mut map = tunnels.write().await;
                 if let Some(info) = map.get_mut(&key) {
                     if !prev_health.is_healthy() {
                         info!("Tunnel {} is now healthy", key);
+                        self.emit(TunnelEvent::Healthy {
+                            tunnel: key.clone(),
+                        });
                     }
                     // Put process back
                     info.process = process_opt;
                     info.health = TunnelHealth::Healthy;
+                    info.last_probe_latency = probe_latency.or(info.last_probe_latency);
                     info.failure_count = 0;
                     info.next_restart_at = None;
                     info.backoff_secs = 1;
@@ -428,6 +1219,7 @@ impl TunnelManager {
                                 info.process = process_opt;
                                 info.health = TunnelHealth::Down;
                                 info.failure_count = failure_count;
+                                info.last_error = Some(failure_reason.to_string());
                                 info.next_restart_at = Some(at);
                                 info.backoff_secs = backoff_secs;
                             }
@@ -437,7 +1229,7 @@ impl TunnelManager {
                     } else {
                         // First time exceeding threshold: compute next_restart_at and kill process once
                         if let Some(proc_to_kill) = process_opt.take()
-                            && let Err(e) = SshClient::kill_process(proc_to_kill).await
+                            && let Err(e) = transport.close(&spec, proc_to_kill).await
                         {
                             error!("Error killing failed tunnel process: {}", e);
                         }
@@ -452,9 +1244,17 @@ impl TunnelManager {
                                 "Tunnel {} failed {} times, scheduling restart in {}s",
                                 key, failure_count, jittered
                             );
-                            info.process = None;
+                            self.emit(TunnelEvent::Down {
+                                tunnel: key.clone(),
+                            });
+                            self.emit(TunnelEvent::RestartScheduled {
+                                tunnel: key.clone(),
+                                at: SystemTime::now() + Duration::from_secs(jittered),
+                            });
+                            info.detach();
                             info.health = TunnelHealth::Down;
                             info.failure_count = failure_count;
+                            info.last_error = Some(failure_reason.to_string());
                             info.next_restart_at = next_restart_at;
                             info.backoff_secs = backoff_secs;
                         }
@@ -462,18 +1262,42 @@ impl TunnelManager {
                     }
 
                     // Try to restart now
-                    match ssh_client.start_forwarding(&spec).await {
+                    let result = self.open_forward(transport, &spec).await;
+                    self.record_open_error(&tunnel.remote, &result).await;
+                    let endpoint = self.active_endpoint(&tunnel.remote).await;
+                    match result {
                         Ok(new_proc) => {
                             let mut map = tunnels.write().await;
                             if let Some(info) = map.get_mut(&key) {
                                 info!("Restarted tunnel: {}", key);
-                                info.process = Some(new_proc);
+                                self.emit(TunnelEvent::Restarted {
+                                    tunnel: key.clone(),
+                                });
+                                info.attach(new_proc);
+                                info.endpoint = endpoint;
+                                info.restart_count += 1;
                                 info.health = TunnelHealth::Unknown;
                                 info.failure_count = 0;
                                 info.next_restart_at = None;
                                 info.backoff_secs = 1;
                             }
                         }
+                        Err(e @ StunError::PortInUse { .. }) => {
+                            // Another process took the port; retrying cannot succeed
+                            let mut map = tunnels.write().await;
+                            if let Some(info) = map.get_mut(&key) {
+                                error!("Tunnel {} cannot restart: {}", key, e);
+                                self.emit(TunnelEvent::Failed {
+                                    tunnel: key.clone(),
+                                    error: e.to_string(),
+                                });
+                                info.detach();
+                                info.health = TunnelHealth::Failed;
+                                info.failure_count = failure_count;
+                                info.last_error = Some(e.to_string());
+                                info.next_restart_at = None;
+                            }
+                        }
                         Err(e) => {
                             // Increase backoff and schedule again
                             backoff_secs = (backoff_secs.saturating_mul(2)).min(backoff_max_secs);
@@ -483,9 +1307,19 @@ impl TunnelManager {
                             let mut map = tunnels.write().await;
                             if let Some(info) = map.get_mut(&key) {
                                 error!("Failed to restart tunnel {}: {}", key, e);
-                                info.process = None;
+                                self.emit(TunnelEvent::RestartFailed {
+                                    tunnel: key.clone(),
+                                    error: e.to_string(),
+                                });
+                                self.emit(TunnelEvent::RestartScheduled {
+                                    tunnel: key.clone(),
+                                    at: SystemTime::now() + Duration::from_secs(delay),
+                                });
+                                info.detach();
+                                info.endpoint = endpoint;
                                 info.health = TunnelHealth::Down;
                                 info.failure_count = failure_count;
+                                info.last_error = Some(e.to_string());
                                 info.next_restart_at = Some(when);
                                 info.backoff_secs = backoff_secs;
                             }
@@ -498,10 +1332,15 @@ impl TunnelManager {
                             "Tunnel {} health check failed ({}/{})",
                             key, failure_count, max_failures
                         );
+                        self.emit(TunnelEvent::Degraded {
+                            tunnel: key.clone(),
+                            failures: failure_count,
+                        });
                         // Put process back and update counters
                         info.process = process_opt;
                         info.health = TunnelHealth::Down;
                         info.failure_count = failure_count;
+                        info.last_error = Some(failure_reason.to_string());
                         // retain any existing backoff scheduling
                         info.next_restart_at = next_restart_at;
                         info.backoff_secs = backoff_secs;
@@ -510,15 +1349,22 @@ impl TunnelManager {
             }
         }
     }
+}
 
-    /// Get the status of all tunnels
-    pub async fn get_status(&self) -> HashMap<String, TunnelHealth> {
-        let tunnels = self.tunnels.read().await;
-        tunnels
-            .iter()
-            .map(|(key, info)| (key.clone(), info.health.clone()))
-            .collect()
-    }
+/// Failover bookkeeping of one remote
+#[derive(Debug, Default)]
+struct EndpointState {
+    /// Index into the remote's endpoints, 0 being the primary
+    active: usize,
+    /// Forward starts that failed since the last successful one
+    failed_starts: u32,
+}
+
+/// Number of events buffered per subscriber before it starts lagging
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+fn unknown_tunnel(key: &str) -> StunError {
+    StunError::Tunnel(format!("Unknown tunnel: {key}"))
 }
 
 /// Compute a deterministic jittered delay in seconds for backoff (80%-120%)
@@ -540,25 +1386,21 @@ impl Drop for TunnelManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{ForwardingMode, RemoteConfig};
+    use crate::config::{
+        FailoverPolicy, ForwardingEntry, ForwardingMode, HostKeyPolicy, RemoteConfig, TunnelEntry,
+    };
 
     fn create_test_config() -> Config {
         Config {
             mode: ForwardingMode::Local,
             remote: RemoteConfig {
                 host: "127.0.0.1".to_string(),
-                port: 22,
                 user: "testuser".to_string(),
-                key: None,
+                ..Default::default()
             },
-            forwarding_list: vec![
-                "18080:127.0.0.1:8080".to_string(),
-                "19000:127.0.0.1:9000".to_string(),
-            ],
+            forwarding_list: vec!["18080:127.0.0.1:8080".into(), "19000:127.0.0.1:9000".into()],
             timeout: Some(1),
-            remote_probes: None,
-            backoff_base_secs: None,
-            backoff_max_secs: None,
+            ..Default::default()
         }
     }
 
@@ -573,9 +1415,311 @@ mod tests {
     #[tokio::test]
     async fn test_invalid_config() {
         let mut config = create_test_config();
-        config.forwarding_list = vec!["invalid".to_string()];
+        config.forwarding_list = vec!["invalid".into()];
 
         let result = TunnelManager::new(config);
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_control_requires_running_manager() {
+        let manager = TunnelManager::new(create_test_config()).unwrap();
+        let spec = ForwardingSpec::parse("18081:127.0.0.1:8081").unwrap();
+
+        assert!(manager.add_tunnel(spec).await.is_err());
+        assert!(manager.remove_tunnel("18080:127.0.0.1:8080").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_runtime_control() {
+        let mut config = create_test_config();
+        // Nothing listens on port 1, so fetching the pinned key fails before ssh starts
+        config.remote.port = 1;
+        config.remote.host_key_policy = HostKeyPolicy::Pinned(vec!["SHA256:test".to_string()]);
+        let mut manager = TunnelManager::new(config).unwrap();
+        let handle = manager.start_background().await.unwrap();
+
+        let spec = ForwardingSpec::parse("18081:127.0.0.1:8081").unwrap();
+        let key = manager.add_tunnel(spec.clone()).await.unwrap();
+        assert_eq!(key, "18081:127.0.0.1:8081");
+        assert!(manager.add_tunnel(spec).await.is_err());
+        assert_eq!(manager.get_status().await.len(), 3);
+
+        manager.pause_tunnel(&key).await.unwrap();
+        let status = manager.tunnel_status(&key).await.unwrap();
+        assert_eq!(status.health, TunnelHealth::Paused);
+        assert_eq!(status.pid, None);
+        manager.resume_tunnel(&key).await.unwrap();
+        assert!(manager.resume_tunnel(&key).await.is_err());
+
+        manager
+            .restart_tunnel("18080:127.0.0.1:8080")
+            .await
+            .unwrap();
+        // The relaunch cannot reach the remote, so it is not counted as a restart
+        let status = manager.tunnel_status("18080:127.0.0.1:8080").await.unwrap();
+        assert_eq!(status.restart_count, 0);
+        assert_eq!(status.health, TunnelHealth::Down);
+        manager.remove_tunnel(&key).await.unwrap();
+        assert!(manager.remove_tunnel(&key).await.is_err());
+        assert_eq!(manager.get_status().await.len(), 2);
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
+
+    #[tokio::test]
+    async fn test_automatic_bind_port() {
+        let mut config = create_test_config();
+        config.remote.port = 1;
+        config.forwarding_list = vec!["auto:127.0.0.1:8080".into()];
+        let mut manager = TunnelManager::new(config).unwrap();
+        let handle = manager.start_background().await.unwrap();
+
+        // The port is picked before ssh runs, so it is known even though ssh fails
+        let key = "auto:127.0.0.1:8080";
+        let addr = manager.local_addr(key).await.unwrap();
+        assert_eq!(addr.ip().to_string(), "127.0.0.1");
+        assert_ne!(addr.port(), 0);
+        let status = manager.tunnel_status(key).await.unwrap();
+        assert_eq!(status.assigned_port, Some(addr.port()));
+        assert_eq!(status.spec.bind_port, 0);
+
+        // Restarts keep the assigned port
+        manager.restart_tunnel(key).await.unwrap();
+        assert_eq!(manager.local_addr(key).await.unwrap(), addr);
+
+        let spec = ForwardingSpec::parse("R:0:127.0.0.1:9000").unwrap();
+        assert!(manager.add_tunnel(spec).await.is_err());
+        assert!(manager.local_addr("missing").await.is_err());
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
+
+    #[tokio::test]
+    async fn test_port_in_use_fails_permanently() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let key = format!("{port}:127.0.0.1:8080");
+
+        let mut config = create_test_config();
+        config.remote.port = 1;
+        config.forwarding_list = vec![key.as_str().into()];
+        let mut manager = TunnelManager::new(config).unwrap();
+        let mut events = manager.subscribe();
+        let handle = manager.start_background().await.unwrap();
+
+        let status = manager.tunnel_status(&key).await.unwrap();
+        assert_eq!(status.health, TunnelHealth::Failed);
+        assert!(status.last_error.unwrap().contains("already in use"));
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            TunnelEvent::Failed { .. }
+        ));
+
+        // Restarting on request checks the port again
+        drop(listener);
+        manager.restart_tunnel(&key).await.unwrap();
+        let status = manager.tunnel_status(&key).await.unwrap();
+        assert_ne!(status.health, TunnelHealth::Failed);
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
+
+    #[tokio::test]
+    async fn test_reload_reconciles_tunnels() {
+        let mut config = create_test_config();
+        config.remote.port = 1;
+        let mut manager = TunnelManager::new(config.clone()).unwrap();
+        let handle = manager.start_background().await.unwrap();
+
+        config.forwarding_list = vec!["18080:127.0.0.1:8080".into(), "18082:127.0.0.1:8082".into()];
+        let summary = manager.reload(config.clone()).await.unwrap();
+        assert_eq!(summary.added, vec!["18082:127.0.0.1:8082".to_string()]);
+        assert_eq!(summary.removed, vec!["19000:127.0.0.1:9000".to_string()]);
+        assert!(summary.restarted.is_empty());
+
+        config.remote.user = "otheruser".to_string();
+        let summary = manager.reload(config.clone()).await.unwrap();
+        assert_eq!(summary.restarted.len(), 2);
+
+        // A tunnel on an unknown remote rejects the whole reload before anything changes
+        let mut broken = config;
+        broken.forwarding_list = vec![ForwardingEntry::Tunnel(TunnelEntry {
+            name: Some("orphan".to_string()),
+            bind: "18083".to_string(),
+            target: Some("127.0.0.1:8083".to_string()),
+            mode: None,
+            remote: Some("missing".to_string()),
+            probe: None,
+            timeout: None,
+            enabled: true,
+            tags: Vec::new(),
+        })];
+        let config = Box::new(broken);
+        let result = manager
+            .send_command(|reply| ControlCommand::Reload { config, reply })
+            .await;
+        assert!(matches!(result, Err(StunError::Tunnel(_))));
+        assert_eq!(manager.get_status().await.len(), 2);
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
+
+    #[tokio::test]
+    async fn test_named_remotes() {
+        let mut config = create_test_config();
+        config.remote.port = 1;
+        let mut staging = config.remote.clone();
+        staging.user = "deploy".to_string();
+        config.remotes.insert("staging".to_string(), staging);
+        config
+            .forwarding_list
+            .push(ForwardingEntry::Tunnel(TunnelEntry {
+                name: Some("staging-db".to_string()),
+                bind: "15432".to_string(),
+                target: Some("127.0.0.1:5432".to_string()),
+                mode: None,
+                remote: Some("staging".to_string()),
+                probe: None,
+                timeout: None,
+                enabled: true,
+                tags: Vec::new(),
+            }));
+        let mut manager = TunnelManager::new(config.clone()).unwrap();
+        let handle = manager.start_background().await.unwrap();
+
+        let status = manager.tunnel_status("staging-db").await.unwrap();
+        assert_eq!(status.remote, "staging");
+        let status = manager.tunnel_status("18080:127.0.0.1:8080").await.unwrap();
+        assert_eq!(status.remote, DEFAULT_REMOTE);
+
+        // Only the tunnels of the changed remote are relaunched
+        config.remotes.get_mut("staging").unwrap().user = "other".to_string();
+        let summary = manager.reload(config).await.unwrap();
+        assert_eq!(summary.restarted, vec!["staging-db".to_string()]);
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_fallback_endpoint() {
+        let mut config = create_test_config();
+        config.remote.port = 1;
+        config.remote.fallbacks = vec![Endpoint {
+            host: "127.0.0.1".to_string(),
+            port: 2,
+        }];
+        config.remote.failover = FailoverPolicy::RoundRobin;
+        config.forwarding_list.truncate(1);
+        let mut manager = TunnelManager::new(config).unwrap();
+        manager.health_check_interval = Duration::from_millis(100);
+        let handle = manager.start_background().await.unwrap();
+
+        let key = "18080:127.0.0.1:8080";
+        let status = manager.tunnel_status(key).await.unwrap();
+        assert_eq!(status.endpoint.as_deref(), Some("127.0.0.1:1"));
+
+        // Nothing listens on either port, so the failed start moves the remote on
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let status = manager.tunnel_status(key).await.unwrap();
+            if status.endpoint.as_deref() == Some("127.0.0.1:2") {
+                break;
+            }
+            assert!(Instant::now() < deadline, "remote did not fail over");
+            sleep(Duration::from_millis(50)).await;
+        }
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
+
+    /// Transport whose forwards start, then drop like ssh failing to reach the remote
+    struct DroppingTransport;
+
+    #[async_trait::async_trait]
+    impl TunnelTransport for DroppingTransport {
+        async fn open(&self, _spec: &ForwardingSpec) -> StunResult<ForwardHandle> {
+            Ok(ForwardHandle::Process(
+                tokio::process::Command::new("sh")
+                    .args(["-c", "exit 255"])
+                    .spawn()?,
+            ))
+        }
+
+        async fn is_alive(&self, _handle: &mut ForwardHandle) -> bool {
+            false
+        }
+
+        async fn close(&self, _spec: &ForwardingSpec, _handle: ForwardHandle) -> StunResult<()> {
+            Ok(())
+        }
+
+        async fn probe_remote(&self, _host: &str, _port: u16) -> StunResult<bool> {
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_when_connection_drops() {
+        let mut config = create_test_config();
+        config.remote.port = 1;
+        config.remote.fallbacks = vec![Endpoint {
+            host: "127.0.0.1".to_string(),
+            port: 2,
+        }];
+        config.remote.failover = FailoverPolicy::RoundRobin;
+        config.forwarding_list.truncate(1);
+        let mut manager =
+            TunnelManager::with_transport(config, Arc::new(DroppingTransport)).unwrap();
+        manager.health_check_interval = Duration::from_millis(100);
+        let handle = manager.start_background().await.unwrap();
+
+        // Every start opens fine, but a forward that exits before it was ever healthy
+        // counts as a failed start all the same
+        let key = "18080:127.0.0.1:8080";
+        let status = manager.tunnel_status(key).await.unwrap();
+        assert_eq!(status.health, TunnelHealth::Unknown);
+        assert_eq!(status.endpoint.as_deref(), Some("127.0.0.1:1"));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let status = manager.tunnel_status(key).await.unwrap();
+            if status.endpoint.as_deref() == Some("127.0.0.1:2") {
+                break;
+            }
+            assert!(Instant::now() < deadline, "remote did not fail over");
+            sleep(Duration::from_millis(50)).await;
+        }
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        let mut config = create_test_config();
+        config.remote.port = 1;
+        config.forwarding_list.truncate(1);
+        let mut manager = TunnelManager::new(config).unwrap();
+        let mut events = manager.subscribe();
+        let handle = manager.start_background().await.unwrap();
+
+        // Started, or Down when no ssh binary is installed
+        let key = "18080:127.0.0.1:8080";
+        assert_eq!(events.recv().await.unwrap().tunnel(), key);
+
+        manager.remove_tunnel(key).await.unwrap();
+        let removed = events.recv().await.unwrap();
+        assert_eq!(removed.tunnel(), key);
+        assert!(matches!(removed, TunnelEvent::Removed { .. }));
+
+        manager.stop().await.unwrap();
+        let _ = handle.await;
+    }
 }
@@ -0,0 +1,646 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use russh::{
+    Channel, ChannelOpenFailure, Disconnect,
+    client::{self, Handle, Msg, Session},
+    keys::{
+        self, HashAlg, PrivateKeyWithHashAlg, PublicKey, PublicKeyOrCertificate,
+        agent::client::AgentClient,
+    },
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, copy_bidirectional},
+    net::{TcpListener, TcpStream},
+    sync::{Mutex, mpsc},
+    task::JoinSet,
+    time::timeout,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{Config, ForwardingMode, HostKeyPolicy, RemoteConfig},
+    error::{StunError, StunResult},
+    forwarding::ForwardingSpec,
+    host_key::{known_hosts_name, known_hosts_path},
+    transport::{ForwardHandle, TaskHandle, TunnelTransport},
+};
+
+/// Interval between keepalives on an idle session; three unanswered ones close it
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+/// Time a forwarded connection gets to open its channel or finish its SOCKS handshake
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Channels the server opens for remote forwards, routed by the remote port they arrived on
+type RemoteForwards = Arc<StdMutex<HashMap<u32, mpsc::UnboundedSender<Channel<Msg>>>>>;
+
+/// In-process SSH transport built on russh.
+///
+/// All forwards share one authenticated session, so no `ssh` binary is needed.
+/// Each forward is served by an async task, which spawns one more task per
+/// forwarded connection; the session itself sends keepalives in the background.
+pub struct NativeTransport {
+    config: Config,
+    session: Mutex<Option<Arc<Handle<Client>>>>,
+    remote_forwards: RemoteForwards,
+}
+
+impl NativeTransport {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            session: Mutex::new(None),
+            remote_forwards: Arc::default(),
+        }
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.config.timeout.unwrap_or(2))
+    }
+
+    /// Get the shared session, connecting first if there is none or it was closed
+    async fn session(&self) -> StunResult<Arc<Handle<Client>>> {
+        let mut guard = self.session.lock().await;
+        if let Some(session) = guard.as_ref().filter(|session| !session.is_closed()) {
+            return Ok(Arc::clone(session));
+        }
+
+        let session = Arc::new(self.connect().await?);
+        *guard = Some(Arc::clone(&session));
+        Ok(session)
+    }
+
+    /// Open and authenticate a session
+    async fn connect(&self) -> StunResult<Handle<Client>> {
+        let remote = &self.config.remote;
+        let address = (remote.host.as_str(), remote.port);
+        let tcp = timeout(self.timeout(), TcpStream::connect(address))
+            .await
+            .map_err(|_| {
+                StunError::Network(format!(
+                    "Connecting to {}:{} timed out",
+                    remote.host, remote.port
+                ))
+            })?
+            .map_err(|e| {
+                StunError::Network(format!(
+                    "Failed to connect to {}:{}: {e}",
+                    remote.host, remote.port
+                ))
+            })?;
+        tcp.set_nodelay(true)?;
+
+        let ssh_config = Arc::new(client::Config {
+            keepalive_interval: Some(KEEPALIVE_INTERVAL),
+            keepalive_max: 3,
+            ..Default::default()
+        });
+        let client = Client {
+            config: self.config.clone(),
+            remote_forwards: Arc::clone(&self.remote_forwards),
+        };
+        let mut session = timeout(
+            self.timeout(),
+            client::connect_stream(ssh_config, tcp, client),
+        )
+        .await
+        .map_err(|_| StunError::Ssh("SSH handshake timed out".to_string()))??;
+
+        authenticate(&mut session, remote).await?;
+        info!(
+            "Connected native SSH session to {}@{}:{}",
+            remote.user, remote.host, remote.port
+        );
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl TunnelTransport for NativeTransport {
+    async fn open(&self, spec: &ForwardingSpec) -> StunResult<ForwardHandle> {
+        if spec.uses_unix_socket() {
+            return Err(StunError::Config(format!(
+                "Forward {} uses a Unix socket, which requires the process transport",
+                spec.to_ssh_arg()
+            )));
+        }
+        let session = self.session().await?;
+        let spec = spec.clone();
+
+        let mode = spec.mode_or(self.config.mode);
+        let task = match mode {
+            ForwardingMode::Local | ForwardingMode::Dynamic => {
+                // Bind up front so conflicts surface as an open error
+                let listener =
+                    TcpListener::bind((spec.effective_bind_address(), spec.bind_port)).await?;
+                info!(
+                    "Started native forwarding: {} {}",
+                    mode.to_ssh_flag(),
+                    spec.to_ssh_arg()
+                );
+                tokio::spawn(serve_local(session, listener, spec))
+            }
+            ForwardingMode::Remote => {
+                let port = u32::from(spec.bind_port);
+                let (sender, channels) = mpsc::unbounded_channel();
+                self.remote_forwards
+                    .lock()
+                    .expect("remote forwards lock poisoned")
+                    .insert(port, sender);
+
+                let requested = timeout(
+                    self.timeout(),
+                    session.tcpip_forward(spec.effective_bind_address(), port),
+                )
+                .await;
+                if !matches!(requested, Ok(Ok(_))) {
+                    self.remote_forwards
+                        .lock()
+                        .expect("remote forwards lock poisoned")
+                        .remove(&port);
+                    let reason = match requested {
+                        Ok(Err(e)) => e.to_string(),
+                        _ => "timed out".to_string(),
+                    };
+                    return Err(StunError::Ssh(format!(
+                        "Remote forwarding {} was rejected: {reason}",
+                        spec.to_ssh_arg()
+                    )));
+                }
+                info!("Started native remote forwarding: {}", spec.to_ssh_arg());
+                tokio::spawn(serve_remote(channels, spec))
+            }
+        };
+
+        Ok(ForwardHandle::Task(TaskHandle::new(task)))
+    }
+
+    async fn is_alive(&self, handle: &mut ForwardHandle) -> bool {
+        match handle {
+            ForwardHandle::Task(task) => !task.is_finished(),
+            ForwardHandle::Process(_) | ForwardHandle::Multiplexed => false,
+        }
+    }
+
+    async fn close(&self, spec: &ForwardingSpec, handle: ForwardHandle) -> StunResult<()> {
+        if let ForwardHandle::Task(task) = handle {
+            task.cancel();
+        }
+        if spec.mode_or(self.config.mode) == ForwardingMode::Remote {
+            let port = u32::from(spec.bind_port);
+            self.remote_forwards
+                .lock()
+                .expect("remote forwards lock poisoned")
+                .remove(&port);
+            let session = self.session.lock().await.clone();
+            if let Some(session) = session.filter(|session| !session.is_closed())
+                && let Err(e) = session
+                    .cancel_tcpip_forward(spec.effective_bind_address(), port)
+                    .await
+            {
+                debug!(
+                    "Failed to cancel remote forwarding {}: {}",
+                    spec.to_ssh_arg(),
+                    e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn connection_alive(&self) -> Option<bool> {
+        // Keepalives run inside the session, which closes once they go unanswered
+        let guard = self.session.lock().await;
+        Some(!guard.as_ref()?.is_closed())
+    }
+
+    async fn reconnect(&self) -> StunResult<()> {
+        if let Some(old) = self.session.lock().await.take() {
+            let _ = old
+                .disconnect(Disconnect::ByApplication, "reconnecting", "en")
+                .await;
+        }
+        self.session().await.map(|_| ())
+    }
+
+    async fn probe_remote(&self, host: &str, port: u16) -> StunResult<bool> {
+        let session = self.session().await?;
+        let opened = timeout(
+            self.timeout(),
+            session.channel_open_direct_tcpip(host, u32::from(port), "127.0.0.1", 0),
+        )
+        .await;
+
+        match opened {
+            Ok(Ok(channel)) => {
+                let _ = channel.close().await;
+                Ok(true)
+            }
+            Ok(Err(russh::Error::ChannelOpenFailure(_))) | Err(_) => Ok(false),
+            Ok(Err(e)) => Err(StunError::Ssh(format!("Remote probe failed: {e}"))),
+        }
+    }
+
+    async fn shutdown(&self) {
+        if let Some(session) = self.session.lock().await.take() {
+            let _ = session
+                .disconnect(Disconnect::ByApplication, "stun shutting down", "en")
+                .await;
+            debug!("Closed native SSH session");
+        }
+    }
+}
+
+impl From<russh::Error> for StunError {
+    fn from(e: russh::Error) -> Self {
+        StunError::Ssh(e.to_string())
+    }
+}
+
+/// Session callbacks: host key verification and channels opened for remote forwards
+struct Client {
+    config: Config,
+    remote_forwards: RemoteForwards,
+}
+
+impl client::Handler for Client {
+    type Error = StunError;
+
+    async fn check_server_key(&mut self, key: &PublicKeyOrCertificate) -> StunResult<bool> {
+        let key = match key {
+            PublicKeyOrCertificate::PublicKey { key, .. } => key.clone(),
+            PublicKeyOrCertificate::Certificate(certificate) => {
+                PublicKey::from(certificate.public_key().clone())
+            }
+        };
+        verify_host_key(&self.config, &key)?;
+        Ok(true)
+    }
+
+    async fn server_channel_open_forwarded_tcpip(
+        &mut self,
+        channel: Channel<Msg>,
+        _connected_address: &str,
+        connected_port: u32,
+        originator_address: &str,
+        originator_port: u32,
+        reply: client::ChannelOpenHandle,
+        _session: &mut Session,
+    ) -> StunResult<()> {
+        let forward = self
+            .remote_forwards
+            .lock()
+            .expect("remote forwards lock poisoned")
+            .get(&connected_port)
+            .cloned();
+        match forward {
+            Some(forward) => {
+                reply.accept().await;
+                if forward.send(channel).is_err() {
+                    debug!("Remote forwarding on port {} is closing", connected_port);
+                }
+            }
+            None => {
+                debug!(
+                    "Rejected connection from {}:{} to unknown remote port {}",
+                    originator_address, originator_port, connected_port
+                );
+                reply
+                    .reject(ChannelOpenFailure::AdministrativelyProhibited)
+                    .await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Authenticate with the configured key, or with the identities of the SSH agent
+async fn authenticate(session: &mut Handle<Client>, remote: &RemoteConfig) -> StunResult<()> {
+    let accepted = match &remote.key {
+        Some(path) => {
+            let key = keys::load_secret_key(path, None)
+                .map_err(|e| StunError::Ssh(format!("Failed to load key {path}: {e}")))?;
+            let hash = rsa_hash(session, key.algorithm().is_rsa()).await?;
+            session
+                .authenticate_publickey(
+                    &remote.user,
+                    PrivateKeyWithHashAlg::new(Arc::new(key), hash),
+                )
+                .await
+                .map_err(|e| StunError::Ssh(format!("Public key authentication failed: {e}")))?
+                .success()
+        }
+        None => authenticate_with_agent(session, &remote.user).await?,
+    };
+
+    if !accepted {
+        return Err(StunError::Ssh(format!(
+            "Authentication as {} was not accepted",
+            remote.user
+        )));
+    }
+    Ok(())
+}
+
+/// Try each identity of the agent behind `SSH_AUTH_SOCK` until one is accepted
+async fn authenticate_with_agent(session: &mut Handle<Client>, user: &str) -> StunResult<bool> {
+    let agent_error =
+        |e: &dyn std::fmt::Display| StunError::Ssh(format!("SSH agent authentication failed: {e}"));
+    let mut agent = AgentClient::connect_env()
+        .await
+        .map_err(|e| agent_error(&e))?;
+    let identities = agent
+        .request_identities()
+        .await
+        .map_err(|e| agent_error(&e))?;
+
+    for identity in identities {
+        let key = identity.public_key().into_owned();
+        let hash = rsa_hash(session, key.algorithm().is_rsa()).await?;
+        let result = session
+            .authenticate_publickey_with(user, key, hash, &mut agent)
+            .await
+            .map_err(|e| agent_error(&e))?;
+        if result.success() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Strongest signature hash the server accepts for RSA keys
+async fn rsa_hash(session: &Handle<Client>, is_rsa: bool) -> StunResult<Option<HashAlg>> {
+    if !is_rsa {
+        return Ok(None);
+    }
+    Ok(session.best_supported_rsa_hash().await?.flatten())
+}
+
+/// Check the key presented during the handshake against the configured policy
+fn verify_host_key(config: &Config, key: &PublicKey) -> StunResult<()> {
+    let (host, port) = (config.remote.host.as_str(), config.remote.port);
+    let name = known_hosts_name(host, port);
+
+    if let HostKeyPolicy::Pinned(fingerprints) = &config.remote.host_key_policy {
+        let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+        if !fingerprints.contains(&fingerprint) {
+            return Err(StunError::HostKey(format!(
+                "{name} presented {fingerprint} but only {} are pinned",
+                fingerprints.join(", ")
+            )));
+        }
+        return Ok(());
+    }
+
+    let path = known_hosts_path(config);
+    match keys::check_known_hosts_path(host, port, key, &path) {
+        Ok(true) => Ok(()),
+        Err(keys::Error::KeyChanged { line }) => Err(StunError::HostKey(format!(
+            "{name} presented a key that does not match line {line} of {}",
+            path.display()
+        ))),
+        Err(e) => Err(StunError::HostKey(format!(
+            "Could not check the host key of {name}: {e}"
+        ))),
+        Ok(false) => match config.remote.host_key_policy {
+            HostKeyPolicy::AcceptNew => {
+                // Appends, so existing (possibly hashed) entries stay untouched
+                keys::known_hosts::learn_known_hosts_path(host, port, key, &path)
+                    .map_err(|e| StunError::HostKey(format!("Failed to record {name}: {e}")))?;
+                warn!("Permanently added {} to {}", name, path.display());
+                Ok(())
+            }
+            _ => Err(StunError::HostKey(format!(
+                "{name} has no entry in known_hosts"
+            ))),
+        },
+    }
+}
+
+/// Accept local connections and tunnel each through a direct-tcpip channel.
+/// Dynamic forwards learn each connection's target from a SOCKS5 request.
+async fn serve_local(session: Arc<Handle<Client>>, listener: TcpListener, spec: ForwardingSpec) {
+    // Dropped along with this task, which aborts every connection it still serves
+    let mut connections = JoinSet::new();
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Listener for {} failed: {}", spec.to_ssh_arg(), e);
+                break;
+            }
+        };
+        while connections.try_join_next().is_some() {}
+
+        debug!("Accepted {} on {}", peer, spec.to_ssh_arg());
+        let session = Arc::clone(&session);
+        let spec = spec.clone();
+        connections.spawn(async move {
+            if let Err(e) = forward_local(&session, stream, peer, &spec).await {
+                debug!(
+                    "Connection from {} on {} ended: {}",
+                    peer,
+                    spec.to_ssh_arg(),
+                    e
+                );
+            }
+        });
+    }
+}
+
+/// Tunnel one local connection to the forward's target, or to the one it asks for over SOCKS5
+async fn forward_local(
+    session: &Handle<Client>,
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    spec: &ForwardingSpec,
+) -> StunResult<()> {
+    let (host, port) = if spec.is_dynamic() {
+        timeout(CONNECTION_TIMEOUT, socks5_accept(&mut stream))
+            .await
+            .map_err(|_| StunError::Network("SOCKS handshake timed out".to_string()))??
+    } else {
+        (spec.remote_host.clone(), spec.remote_port)
+    };
+
+    let opened = timeout(
+        CONNECTION_TIMEOUT,
+        session.channel_open_direct_tcpip(
+            host.as_str(),
+            u32::from(port),
+            peer.ip().to_string(),
+            u32::from(peer.port()),
+        ),
+    )
+    .await;
+    let channel = match opened {
+        Ok(Ok(channel)) => channel,
+        failed => {
+            if spec.is_dynamic() {
+                // Connection refused
+                let _ = socks5_reply(&mut stream, 0x05).await;
+            }
+            let reason = match failed {
+                Ok(Err(e)) => e.to_string(),
+                _ => "timed out".to_string(),
+            };
+            warn!("Failed to open channel to {}:{}: {}", host, port, reason);
+            return Ok(());
+        }
+    };
+    if spec.is_dynamic() {
+        socks5_reply(&mut stream, 0x00).await?;
+    }
+
+    copy_bidirectional(&mut stream, &mut channel.into_stream()).await?;
+    Ok(())
+}
+
+/// Read a SOCKS5 greeting and CONNECT request, returning the requested target
+async fn socks5_accept<S>(stream: &mut S) -> StunResult<(String, u16)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let invalid = |msg: &str| StunError::Network(format!("Invalid SOCKS5 request: {msg}"));
+
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != 0x05 {
+        return Err(invalid("not a SOCKS5 client"));
+    }
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&0x00) {
+        stream.write_all(&[0x05, 0xff]).await?;
+        return Err(invalid("client requires authentication"));
+    }
+    stream.write_all(&[0x05, 0x00]).await?;
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[1] != 0x01 {
+        // Command not supported
+        socks5_reply(stream, 0x07).await?;
+        return Err(invalid("only CONNECT is supported"));
+    }
+    let host = match request[3] {
+        0x01 => {
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+            std::net::Ipv4Addr::from(addr).to_string()
+        }
+        0x03 => {
+            let len = stream.read_u8().await?;
+            let mut name = vec![0u8; len as usize];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name).map_err(|_| invalid("host name is not UTF-8"))?
+        }
+        0x04 => {
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+            std::net::Ipv6Addr::from(addr).to_string()
+        }
+        _ => {
+            // Address type not supported
+            socks5_reply(stream, 0x08).await?;
+            return Err(invalid("unknown address type"));
+        }
+    };
+    let port = stream.read_u16().await?;
+
+    Ok((host, port))
+}
+
+/// Send a SOCKS5 reply with an unspecified bound address
+async fn socks5_reply<S>(stream: &mut S, code: u8) -> std::io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    stream
+        .write_all(&[0x05, code, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        .await
+}
+
+/// Connect each channel the server opens for a remote forward to the local target
+async fn serve_remote(mut channels: mpsc::UnboundedReceiver<Channel<Msg>>, spec: ForwardingSpec) {
+    // Dropped along with this task, which aborts every connection it still serves
+    let mut connections = JoinSet::new();
+    while let Some(channel) = channels.recv().await {
+        while connections.try_join_next().is_some() {}
+
+        let spec = spec.clone();
+        connections.spawn(async move {
+            let target = (spec.remote_host.as_str(), spec.remote_port);
+            match TcpStream::connect(target).await {
+                Ok(mut stream) => {
+                    if let Err(e) =
+                        copy_bidirectional(&mut stream, &mut channel.into_stream()).await
+                    {
+                        debug!("Connection to {}:{} ended: {}", target.0, target.1, e);
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to connect to {}:{}: {}", target.0, target.1, e);
+                    let _ = channel.close().await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{RemoteConfig, TransportKind};
+
+    #[tokio::test]
+    async fn test_socks5_accept() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        client.write_all(&[0x05, 0x01, 0x00]).await.unwrap();
+        client
+            .write_all(&[0x05, 0x01, 0x00, 0x03, 11])
+            .await
+            .unwrap();
+        client.write_all(b"example.com").await.unwrap();
+        client.write_all(&443u16.to_be_bytes()).await.unwrap();
+
+        let target = socks5_accept(&mut server).await.unwrap();
+        assert_eq!(target, ("example.com".to_string(), 443));
+
+        let mut method = [0u8; 2];
+        client.read_exact(&mut method).await.unwrap();
+        assert_eq!(method, [0x05, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn test_open_fails_without_server() {
+        let config = Config {
+            mode: ForwardingMode::Local,
+            remote: RemoteConfig {
+                host: "127.0.0.1".to_string(),
+                // Nothing listens on port 1, so the connection is refused
+                port: 1,
+                user: "testuser".to_string(),
+                transport: TransportKind::Native,
+                ..Default::default()
+            },
+            forwarding_list: vec!["18280:127.0.0.1:8080".into()],
+            timeout: Some(1),
+            ..Default::default()
+        };
+        let transport = NativeTransport::new(config);
+
+        assert_eq!(transport.connection_alive().await, None);
+        let spec = ForwardingSpec::parse("18280:127.0.0.1:8080").unwrap();
+        assert!(matches!(
+            transport.open(&spec).await,
+            Err(StunError::Network(_))
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+use std::{io::ErrorKind, net::TcpListener};
+
+use crate::{
+    error::{StunError, StunResult},
+    forwarding::format_host_port,
+};
+
+/// Check that `port` can be bound on `address` before ssh is asked to listen there.
+///
+/// Only a port that is already taken fails the check; other bind errors, such as a
+/// privileged port, are left for ssh to report.
+pub fn check_available(address: &str, port: u16) -> StunResult<()> {
+    match TcpListener::bind((socket_host(address), port)) {
+        Err(e) if e.kind() == ErrorKind::AddrInUse => Err(StunError::PortInUse {
+            address: format_host_port(address, port),
+            owner: port_owner(port),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Check that nothing serves the Unix socket at `path` before ssh is asked to listen there.
+///
+/// ssh unlinks an existing socket file before binding it, which is only safe for a
+/// stale one left behind by an earlier run.
+#[cfg(unix)]
+pub fn check_socket_available(path: &str) -> StunResult<()> {
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(StunError::PortInUse {
+            address: path.to_string(),
+            owner: None,
+        });
+    }
+    Ok(())
+}
+
+/// Unix sockets are not available on this platform, so there is nothing to check
+#[cfg(not(unix))]
+pub fn check_socket_available(_path: &str) -> StunResult<()> {
+    Ok(())
+}
+
+/// Ask the OS for a port that is free on `address`
+pub fn free_port(address: &str) -> StunResult<u16> {
+    let address = socket_host(address);
+    let listener = TcpListener::bind((address, 0))
+        .map_err(|e| StunError::Tunnel(format!("Failed to pick a free port on {address}: {e}")))?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// Bind address in the form the socket APIs take, `*` being the wildcard
+pub fn socket_host(address: &str) -> &str {
+    if address == "*" { "0.0.0.0" } else { address }
+}
+
+/// Process listening on a local TCP `port`, as `name (pid N)`.
+///
+/// Read from /proc, so it is only known on Linux and for processes whose file
+/// descriptors the current user may inspect.
+#[cfg(target_os = "linux")]
+pub fn port_owner(port: u16) -> Option<String> {
+    let inodes: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|table| listening_inodes(&table, port))
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+    let targets: Vec<String> = inodes
+        .iter()
+        .map(|inode| format!("socket:[{inode}]"))
+        .collect();
+
+    for process in std::fs::read_dir("/proc").ok()?.flatten() {
+        let file_name = process.file_name();
+        let Some(pid) = file_name.to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let owns_socket = fds.flatten().any(|fd| {
+            std::fs::read_link(fd.path()).is_ok_and(|link| {
+                targets
+                    .iter()
+                    .any(|target| link.as_os_str() == target.as_str())
+            })
+        });
+        if owns_socket {
+            let name = std::fs::read_to_string(process.path().join("comm"))
+                .map(|comm| comm.trim().to_string())
+                .unwrap_or_else(|_| "unknown".to_string());
+            return Some(format!("{name} (pid {pid})"));
+        }
+    }
+    None
+}
+
+/// Process listening on a local TCP `port`; not available on this platform
+#[cfg(not(target_os = "linux"))]
+pub fn port_owner(_port: u16) -> Option<String> {
+    None
+}
+
+/// Socket inodes in a /proc/net/tcp table that listen on `port`
+#[cfg(target_os = "linux")]
+fn listening_inodes(table: &str, port: u16) -> Vec<String> {
+    const LISTEN: &str = "0A";
+
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (_, local_port) = fields.get(1)?.rsplit_once(':')?;
+            let listening =
+                u16::from_str_radix(local_port, 16).ok()? == port && *fields.get(3)? == LISTEN;
+            listening.then(|| fields.get(9).map(|inode| inode.to_string()))?
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_in_use() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        match check_available("127.0.0.1", port) {
+            Err(StunError::PortInUse { address, owner }) => {
+                assert_eq!(address, format!("127.0.0.1:{port}"));
+                #[cfg(target_os = "linux")]
+                assert!(
+                    owner
+                        .unwrap()
+                        .contains(&format!("pid {}", std::process::id()))
+                );
+                #[cfg(not(target_os = "linux"))]
+                let _ = owner;
+            }
+            other => panic!("expected PortInUse, got {other:?}"),
+        }
+
+        drop(listener);
+        assert!(check_available("127.0.0.1", port).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_in_use() {
+        let path = std::env::temp_dir().join(format!("stun-test-{}.sock", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        assert!(matches!(
+            check_socket_available(path_str),
+            Err(StunError::PortInUse { .. })
+        ));
+
+        // The socket file outlives its listener and may be replaced
+        drop(listener);
+        assert!(path.exists());
+        assert!(check_socket_available(path_str).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,27 +1,232 @@
-use std::{path::Path, process::Stdio};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
 
-use tokio::process::{Child, Command};
+use async_trait::async_trait;
+use tokio::{
+    io::AsyncReadExt,
+    process::{Child, Command},
+    sync::{Mutex, OnceCell},
+    time::{Instant, sleep, timeout},
+};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    config::Config,
+    config::{Config, JumpHost, ensure_private_dir, private_dir_path},
     error::{StunError, StunResult},
     forwarding::ForwardingSpec,
+    host_key,
+    transport::{ForwardHandle, TunnelTransport},
 };
 
 /// SSH client wrapper for port forwarding
 pub struct SshClient {
     config: Config,
+    /// Shared master connection, present when `remote.multiplex` is enabled
+    master: Option<MasterConnection>,
+    /// Set once the remote's host key passed the configured policy
+    host_key_verified: OnceCell<()>,
+}
+
+/// OpenSSH ControlMaster process that carries every forward of a remote
+struct MasterConnection {
+    control_path: PathBuf,
+    process: Mutex<Option<Child>>,
 }
 
 impl SshClient {
     /// Create a new SSH client with the given configuration
     pub fn new(config: Config) -> Self {
-        Self { config }
+        let master = config.remote.multiplex.then(|| MasterConnection {
+            control_path: control_path_for(&config),
+            process: Mutex::new(None),
+        });
+        Self {
+            config,
+            master,
+            host_key_verified: OnceCell::new(),
+        }
+    }
+
+    /// Check a pinned host key before the first connection so a mismatch fails with
+    /// [`StunError::HostKey`]; ssh enforces the policy on every connection.
+    pub async fn verify_host_key(&self) -> StunResult<()> {
+        self.host_key_verified
+            .get_or_try_init(|| host_key::verify(&self.config))
+            .await
+            .map(|_| ())
+    }
+
+    /// Returns true if forwards share a single master connection
+    pub fn is_multiplexed(&self) -> bool {
+        self.master.is_some()
+    }
+
+    /// Liveness of the master connection; None when not multiplexing
+    pub async fn master_alive(&self) -> Option<bool> {
+        let master = self.master.as_ref()?;
+        let mut process = master.process.lock().await;
+        let alive = match process.as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            None => false,
+        };
+        Some(alive)
+    }
+
+    /// Start the master connection if it is not running and wait for its control socket
+    pub async fn ensure_master(&self) -> StunResult<()> {
+        let Some(master) = &self.master else {
+            return Ok(());
+        };
+
+        let mut process = master.process.lock().await;
+        if let Some(child) = process.as_mut()
+            && matches!(child.try_wait(), Ok(None))
+        {
+            return Ok(());
+        }
+        self.verify_host_key().await?;
+
+        if let Some(dir) = master.control_path.parent() {
+            ensure_private_dir(dir)?;
+        }
+        // A stale socket from a crashed master would make the new one refuse to start
+        let _ = std::fs::remove_file(&master.control_path);
+
+        let mut cmd = Command::new("ssh");
+        cmd.args(["-M", "-S"]).arg(&master.control_path).args([
+            "-N",
+            "-T",
+            "-o",
+            "ServerAliveInterval=30",
+            "-o",
+            "ExitOnForwardFailure=yes",
+            "-o",
+            "ControlPersist=no",
+        ]);
+        cmd.args(host_key::ssh_options(&self.config));
+        self.add_connection_args(&mut cmd);
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        set_parent_death_signal(&mut cmd);
+
+        debug!("Starting SSH master connection: {:?}", cmd);
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| StunError::Ssh(format!("Failed to start SSH master connection: {e}")))?;
+
+        // Forwards can only be added once the control socket exists
+        let deadline =
+            Instant::now() + Duration::from_secs(self.config.timeout.unwrap_or(2).max(1) * 5);
+        while !master.control_path.exists() {
+            if let Ok(Some(status)) = child.try_wait() {
+                let stderr = read_stderr(&mut child).await;
+                return Err(host_key::rejection_error(&stderr).unwrap_or_else(|| {
+                    StunError::Ssh(format!(
+                        "SSH master connection exited with status {status}: {}",
+                        stderr.trim()
+                    ))
+                }));
+            }
+            if Instant::now() >= deadline {
+                let _ = Self::kill_process(child).await;
+                return Err(StunError::Ssh(
+                    "Timed out waiting for SSH master connection".to_string(),
+                ));
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+
+        info!(
+            "Started SSH master connection to {}@{}",
+            self.config.remote.user, self.config.remote.host
+        );
+        *process = Some(child);
+        Ok(())
+    }
+
+    /// Stop the master connection and with it every multiplexed forward
+    pub async fn stop_master(&self) {
+        let Some(master) = &self.master else {
+            return;
+        };
+        if let Some(child) = master.process.lock().await.take() {
+            let _ = Self::kill_process(child).await;
+        }
+        let _ = std::fs::remove_file(&master.control_path);
+    }
+
+    /// Run `ssh -O <operation>` against the master connection
+    async fn run_control(&self, operation: &str, spec: Option<&ForwardingSpec>) -> StunResult<()> {
+        let output = Command::new("ssh")
+            .args(self.control_args(operation, spec))
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| StunError::Ssh(format!("Failed to run ssh -O {operation}: {e}")))?;
+
+        if !output.status.success() {
+            return Err(StunError::Ssh(format!(
+                "ssh -O {operation} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Arguments for a ControlMaster request, e.g. `-S path -O forward -L spec user@host`
+    fn control_args(&self, operation: &str, spec: Option<&ForwardingSpec>) -> Vec<String> {
+        let control_path = self
+            .master
+            .as_ref()
+            .map(|master| master.control_path.display().to_string())
+            .unwrap_or_default();
+
+        let mut args = vec![
+            "-S".to_string(),
+            control_path,
+            "-O".to_string(),
+            operation.to_string(),
+        ];
+        if let Some(spec) = spec {
+            args.push(spec.mode_or(self.config.mode).to_ssh_flag().to_string());
+            args.push(spec.to_ssh_arg());
+        }
+        args.push(format!(
+            "{}@{}",
+            self.config.remote.user, self.config.remote.host
+        ));
+        args
+    }
+
+    /// Add jump host, key, port and target arguments shared by all connections to the remote
+    fn add_connection_args(&self, cmd: &mut Command) {
+        cmd.args(jump_args(&self.config.remote.jump_hosts));
+        if let Some(key_path) = &self.config.remote.key {
+            if Path::new(key_path).exists() {
+                cmd.args(["-i", key_path]);
+            } else {
+                warn!("Private key file does not exist: {}", key_path);
+            }
+        }
+        if self.config.remote.port != 22 {
+            cmd.args(["-p", &self.config.remote.port.to_string()]);
+        }
+        cmd.arg(format!(
+            "{}@{}",
+            self.config.remote.user, self.config.remote.host
+        ));
     }
 
     /// Start an SSH process with port forwarding
     pub async fn start_forwarding(&self, spec: &ForwardingSpec) -> StunResult<Child> {
+        self.verify_host_key().await?;
         let mut cmd = Command::new("ssh");
 
         // Base SSH options
@@ -31,15 +236,23 @@ impl SshClient {
             "-o",
             "ServerAliveInterval=30",
             "-o",
-            "StrictHostKeyChecking=no",
-            "-o",
             "ExitOnForwardFailure=yes",
         ]);
+        cmd.args(host_key::ssh_options(&self.config));
+
+        // A socket left behind by a previous run would make the listener fail. The
+        // manager refuses to launch over one that is still served.
+        if spec.bind_socket.is_some() {
+            cmd.args(["-o", "StreamLocalBindUnlink=yes"]);
+        }
 
         // Add forwarding flag and specification
-        cmd.arg(self.config.mode.to_ssh_flag());
+        cmd.arg(spec.mode_or(self.config.mode).to_ssh_flag());
         cmd.arg(spec.to_ssh_arg());
 
+        // Route the connection through jump hosts, if any
+        cmd.args(jump_args(&self.config.remote.jump_hosts));
+
         // Add private key if specified
         if let Some(key_path) = &self.config.remote.key {
             if Path::new(key_path).exists() {
@@ -63,19 +276,7 @@ impl SshClient {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        // Set parent death signal on Linux to avoid zombie processes
-        #[cfg(target_os = "linux")]
-        unsafe {
-            use std::os::unix::process::CommandExt;
-            cmd.pre_exec(|| {
-                // PR_SET_PDEATHSIG = 1
-                let ret = libc::prctl(1, libc::SIGTERM, 0, 0, 0);
-                if ret != 0 {
-                    return Err(std::io::Error::last_os_error());
-                }
-                Ok(())
-            });
-        }
+        set_parent_death_signal(&mut cmd);
 
         debug!("Starting SSH command: {:?}", cmd);
 
@@ -118,13 +319,17 @@ impl SshClient {
             "-o".to_string(),
             "ServerAliveInterval=30".to_string(),
             "-o".to_string(),
-            "StrictHostKeyChecking=no".to_string(),
-            "-o".to_string(),
             "ExitOnForwardFailure=yes".to_string(),
         ]);
+        parts.extend(host_key::ssh_options(&self.config));
 
-        parts.push(self.config.mode.to_ssh_flag().to_string());
+        parts.push(spec.mode_or(self.config.mode).to_ssh_flag().to_string());
         parts.push(spec.to_ssh_arg());
+        parts.extend(
+            jump_args(&self.config.remote.jump_hosts)
+                .iter()
+                .map(|arg| shell_quote(arg)),
+        );
 
         if let Some(key_path) = &self.config.remote.key {
             parts.push("-i".to_string());
@@ -142,11 +347,6 @@ impl SshClient {
         parts.join(" ")
     }
 
-    /// Returns true if the client is configured for local (-L) forwarding
-    pub fn is_local_mode(&self) -> bool {
-        matches!(self.config.mode, crate::config::ForwardingMode::Local)
-    }
-
     /// Attempt a remote TCP connection to host:port via the SSH server.
     /// This runs a small shell test remotely. Returns true on success.
     pub async fn remote_tcp_probe(&self, host: &str, port: u16) -> StunResult<bool> {
@@ -162,6 +362,7 @@ impl SshClient {
         // Build: ssh [opts] user@host sh -lc 'nc -z -w <timeout> <host> <port> || /dev/tcp'
         // We try netcat first; if unavailable, try bash /dev/tcp if available.
         let timeout_secs = self.config.timeout.unwrap_or(2);
+        self.verify_host_key().await?;
 
         let mut cmd = Command::new("ssh");
         // base options similar to start_forwarding
@@ -170,10 +371,10 @@ impl SshClient {
             "-o",
             "ServerAliveInterval=30",
             "-o",
-            "StrictHostKeyChecking=no",
-            "-o",
             "ExitOnForwardFailure=yes",
         ]);
+        cmd.args(host_key::ssh_options(&self.config));
+        cmd.args(jump_args(&self.config.remote.jump_hosts));
         if let Some(key_path) = &self.config.remote.key
             && Path::new(key_path).exists()
         {
@@ -191,49 +392,209 @@ impl SshClient {
         );
         cmd.arg(script);
 
-        let status = cmd
-            .status()
+        let output = cmd
+            .output()
             .await
             .map_err(|e| StunError::Ssh(format!("Failed to run remote probe: {e}")))?;
 
-        Ok(status.success())
+        if let Some(e) = host_key::rejection_error(&String::from_utf8_lossy(&output.stderr)) {
+            return Err(e);
+        }
+        Ok(output.status.success())
     }
+}
 
-    /// Lookup a configured remote probe target for the given spec (by its to_ssh_arg() string)
-    pub fn remote_probe_target(&self, spec: &ForwardingSpec) -> Option<(String, u16)> {
-        let key = spec.to_ssh_arg();
-        if let Some(map) = &self.config.remote_probes
-            && let Some(target) = map.get(&key)
-        {
-            // Split last ':'
-            let (host, port_str) = target.rsplit_once(':')?;
-            if let Ok(port) = port_str.parse::<u16>() {
-                return Some((host.to_string(), port));
+#[async_trait]
+impl TunnelTransport for SshClient {
+    /// Open a forward as a dedicated process, or on the master connection when multiplexing
+    async fn open(&self, spec: &ForwardingSpec) -> StunResult<ForwardHandle> {
+        if self.master.is_none() {
+            return self
+                .start_forwarding(spec)
+                .await
+                .map(ForwardHandle::Process);
+        }
+
+        self.ensure_master().await?;
+        self.run_control("forward", Some(spec)).await?;
+        info!("Added multiplexed forwarding: {}", spec.to_ssh_arg());
+        Ok(ForwardHandle::Multiplexed)
+    }
+
+    async fn is_alive(&self, handle: &mut ForwardHandle) -> bool {
+        match handle {
+            ForwardHandle::Process(process) => match process.try_wait() {
+                Ok(Some(status)) => {
+                    warn!("SSH process exited with status: {}", status);
+                    false
+                }
+                Ok(None) => true,
+                Err(e) => {
+                    warn!("Error checking SSH process status: {}", e);
+                    false
+                }
+            },
+            ForwardHandle::Multiplexed => self.master_alive().await == Some(true),
+            ForwardHandle::Task(task) => !task.is_finished(),
+        }
+    }
+
+    async fn close(&self, spec: &ForwardingSpec, handle: ForwardHandle) -> StunResult<()> {
+        match handle {
+            ForwardHandle::Process(process) => Self::kill_process(process).await,
+            ForwardHandle::Multiplexed => {
+                // A dead master has already dropped the forward
+                if self.master_alive().await == Some(true) {
+                    self.run_control("cancel", Some(spec)).await?;
+                }
+                Ok(())
+            }
+            ForwardHandle::Task(task) => {
+                task.cancel();
+                Ok(())
             }
         }
-        None
     }
+
+    async fn connection_alive(&self) -> Option<bool> {
+        self.master_alive().await
+    }
+
+    async fn reconnect(&self) -> StunResult<()> {
+        self.ensure_master().await
+    }
+
+    async fn probe_remote(&self, host: &str, port: u16) -> StunResult<bool> {
+        self.remote_tcp_probe(host, port).await
+    }
+
+    async fn shutdown(&self) {
+        self.stop_master().await;
+        host_key::remove_pinned(&self.config).await;
+    }
+}
+
+/// Arguments that relay the connection through `jump_hosts`.
+///
+/// `-J` covers hops that use ssh's default identities; as soon as one hop names
+/// a key, the chain is spelled out as nested `ProxyCommand`s instead.
+fn jump_args(jump_hosts: &[JumpHost]) -> Vec<String> {
+    if jump_hosts.is_empty() {
+        return Vec::new();
+    }
+    if jump_hosts.iter().all(|jump| jump.key.is_none()) {
+        let chain: Vec<String> = jump_hosts.iter().map(JumpHost::destination).collect();
+        return vec!["-J".to_string(), chain.join(",")];
+    }
+    vec![
+        "-o".to_string(),
+        format!("ProxyCommand={}", proxy_command(jump_hosts)),
+    ]
+}
+
+/// `ssh -W %h:%p` through the last hop, reaching it via the hops before it
+fn proxy_command(jump_hosts: &[JumpHost]) -> String {
+    let Some((last, before)) = jump_hosts.split_last() else {
+        return String::new();
+    };
+    let mut parts = vec!["ssh".to_string()];
+    if let Some(key) = &last.key {
+        parts.extend(["-i".to_string(), shell_quote(key)]);
+    }
+    if last.port != 22 {
+        parts.extend(["-p".to_string(), last.port.to_string()]);
+    }
+    if !before.is_empty() {
+        // ssh expands %-tokens once per level, so inner tokens are escaped
+        let inner = proxy_command(before).replace('%', "%%");
+        parts.extend([
+            "-o".to_string(),
+            shell_quote(&format!("ProxyCommand={inner}")),
+        ]);
+    }
+    let destination = match &last.user {
+        Some(user) => format!("{user}@{}", last.host),
+        None => last.host.clone(),
+    };
+    parts.extend([
+        "-W".to_string(),
+        "%h:%p".to_string(),
+        shell_quote(&destination),
+    ]);
+    parts.join(" ")
+}
+
+/// Quote an argument for `sh`, leaving plain words untouched
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Per-remote ControlMaster socket path in the private per-user directory, since the
+/// socket opens channels on the authenticated connection. Kept short to stay under the
+/// Unix socket path limit.
+fn control_path_for(config: &Config) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    config.remote.user.hash(&mut hasher);
+    config.remote.host.hash(&mut hasher);
+    config.remote.port.hash(&mut hasher);
+    private_dir_path().join(format!(
+        "stun-{}-{:x}.ctl",
+        std::process::id(),
+        hasher.finish()
+    ))
+}
+
+/// Everything an exited ssh process wrote to its piped stderr
+pub(crate) async fn read_stderr(process: &mut Child) -> String {
+    let mut stderr = String::new();
+    if let Some(mut pipe) = process.stderr.take() {
+        // A ProxyCommand left behind may hold the pipe open
+        let _ = timeout(Duration::from_secs(1), pipe.read_to_string(&mut stderr)).await;
+    }
+    stderr
+}
+
+/// Set parent death signal on Linux to avoid orphaned ssh processes
+fn set_parent_death_signal(cmd: &mut Command) {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        cmd.pre_exec(|| {
+            // PR_SET_PDEATHSIG = 1
+            let ret = libc::prctl(1, libc::SIGTERM, 0, 0, 0);
+            if ret != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = cmd;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{ForwardingMode, RemoteConfig};
+    use crate::config::{ForwardingMode, HostKeyPolicy, RemoteConfig};
 
     fn create_test_config() -> Config {
         Config {
             mode: ForwardingMode::Local,
             remote: RemoteConfig {
                 host: "example.com".to_string(),
-                port: 22,
                 user: "testuser".to_string(),
                 key: Some("/path/to/key".to_string()),
+                ..Default::default()
             },
-            forwarding_list: vec![],
             timeout: Some(5),
-            remote_probes: None,
-            backoff_base_secs: None,
-            backoff_max_secs: None,
+            ..Default::default()
         }
     }
 
@@ -250,6 +611,77 @@ mod tests {
         assert!(cmd.contains("8080:127.0.0.1:9000"));
         assert!(cmd.contains("-i /path/to/key"));
         assert!(cmd.contains("testuser@example.com"));
+        assert!(cmd.contains("StrictHostKeyChecking=accept-new"));
+        assert!(!cmd.contains("StrictHostKeyChecking=no"));
+    }
+
+    #[test]
+    fn test_build_command_string_jump_hosts() {
+        let mut config = create_test_config();
+        config.remote.jump_hosts = vec![
+            JumpHost {
+                host: "bastion.example.com".to_string(),
+                port: 2222,
+                user: Some("jump".to_string()),
+                key: None,
+            },
+            JumpHost {
+                host: "inner.example.com".to_string(),
+                port: 22,
+                user: None,
+                key: None,
+            },
+        ];
+        let spec = ForwardingSpec::parse("8080:127.0.0.1:9000").unwrap();
+
+        let cmd = SshClient::new(config.clone()).build_command_string(&spec);
+        assert!(cmd.contains("-J jump@bastion.example.com:2222,inner.example.com"));
+
+        // A per-hop key needs a ProxyCommand chain, innermost hop first
+        config.remote.jump_hosts[1].key = Some("/keys/inner".to_string());
+        let cmd = SshClient::new(config).build_command_string(&spec);
+        assert!(!cmd.contains("-J"));
+        assert!(cmd.contains(
+            "'ProxyCommand=ssh -i /keys/inner -o '\\''ProxyCommand=ssh -p 2222 -W %%h:%%p \
+             jump@bastion.example.com'\\'' -W %h:%p inner.example.com'"
+        ));
+        assert!(cmd.ends_with("testuser@example.com"));
+    }
+
+    #[test]
+    fn test_build_command_string_dynamic() {
+        let mut config = create_test_config();
+        config.mode = ForwardingMode::Dynamic;
+
+        let client = SshClient::new(config);
+        let spec = ForwardingSpec::parse("127.0.0.1:1080").unwrap();
+        let cmd = client.build_command_string(&spec);
+
+        assert!(cmd.contains("-D 127.0.0.1:1080"));
+    }
+
+    #[test]
+    fn test_build_command_string_mode_prefix() {
+        let client = SshClient::new(create_test_config());
+        let spec = ForwardingSpec::parse("R:9000:127.0.0.1:9000").unwrap();
+        let cmd = client.build_command_string(&spec);
+
+        assert!(cmd.contains("-R 9000:127.0.0.1:9000"));
+        assert!(!cmd.contains("-L"));
+    }
+
+    #[test]
+    fn test_build_command_string_host_key_policy() {
+        let mut config = create_test_config();
+        config.remote.host_key_policy = HostKeyPolicy::Strict;
+        config.remote.known_hosts = Some("/etc/stun/known_hosts".to_string());
+
+        let client = SshClient::new(config);
+        let spec = ForwardingSpec::parse("8080:127.0.0.1:9000").unwrap();
+        let cmd = client.build_command_string(&spec);
+
+        assert!(cmd.contains("-o StrictHostKeyChecking=yes"));
+        assert!(cmd.contains("-o UserKnownHostsFile=/etc/stun/known_hosts"));
     }
 
     #[test]
@@ -267,4 +699,33 @@ mod tests {
         assert!(cmd.contains("-p 2222"));
         assert!(cmd.contains("0.0.0.0:8080:192.168.1.10:9000"));
     }
+
+    #[test]
+    fn test_multiplexed_control_args() {
+        let mut config = create_test_config();
+        let plain = SshClient::new(config.clone());
+        assert!(!plain.is_multiplexed());
+
+        config.remote.multiplex = true;
+        let client = SshClient::new(config);
+        assert!(client.is_multiplexed());
+
+        let spec = ForwardingSpec::parse("8080:127.0.0.1:9000").unwrap();
+        let args = client.control_args("forward", Some(&spec));
+        assert_eq!(args[0], "-S");
+        assert!(args[1].ends_with(".ctl"));
+        assert_eq!(
+            args[2..],
+            [
+                "-O",
+                "forward",
+                "-L",
+                "8080:127.0.0.1:9000",
+                "testuser@example.com"
+            ]
+        );
+
+        let args = client.control_args("check", None);
+        assert_eq!(args[2..], ["-O", "check", "testuser@example.com"]);
+    }
 }
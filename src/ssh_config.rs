@@ -0,0 +1,403 @@
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+use crate::{
+    config::{
+        Config, ForwardingMode, HostKeyPolicy, JumpHost, RemoteConfig, expand_home, home_dir,
+    },
+    error::{StunError, StunResult},
+};
+
+/// Default OpenSSH user configuration file, `~/.ssh/config`
+pub fn default_path() -> PathBuf {
+    home_dir().join(".ssh").join("config")
+}
+
+/// Build a configuration from the `Host` blocks of an ssh_config file that apply to `host`.
+///
+/// As in ssh, the first value found for a directive wins, while forwards are
+/// collected from every matching block. `%` tokens in `HostName`, `IdentityFile` and
+/// `UserKnownHostsFile` are expanded; a value with a token stun cannot resolve is
+/// skipped with a warning. `Match` blocks and `Include` are not supported.
+pub fn load(path: &Path, host: &str) -> StunResult<Config> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        StunError::Config(format!("Failed to read ssh config {}: {e}", path.display()))
+    })?;
+    parse(&content, host)
+}
+
+/// Build a configuration for `host` from ssh_config text
+pub fn parse(content: &str, host: &str) -> StunResult<Config> {
+    let mut settings = HostSettings::default();
+    // Directives before the first Host line apply to every host
+    let mut active = true;
+
+    for (index, line) in content.lines().enumerate() {
+        let Some((keyword, value)) = split_directive(line) else {
+            continue;
+        };
+        let keyword = keyword.to_ascii_lowercase();
+        match keyword.as_str() {
+            "host" => active = host_matches(value, host),
+            "match" => {
+                warn!(
+                    "Ignoring Match block on line {} of ssh config: Match is not supported",
+                    index + 1
+                );
+                active = false;
+            }
+            _ if !active => {}
+            "include" => debug!("Ignoring Include on line {} of ssh config", index + 1),
+            _ => settings
+                .apply(&keyword, value)
+                .map_err(|e| StunError::Config(format!("ssh config line {}: {e}", index + 1)))?,
+        }
+    }
+
+    settings.into_config(host)
+}
+
+/// Values collected from the blocks that match the requested host
+#[derive(Debug, Default)]
+struct HostSettings {
+    host_name: Option<String>,
+    user: Option<String>,
+    port: Option<u16>,
+    identity_file: Option<String>,
+    proxy_jump: Option<String>,
+    strict_host_key_checking: Option<String>,
+    user_known_hosts_file: Option<String>,
+    forwards: Vec<String>,
+}
+
+impl HostSettings {
+    fn apply(&mut self, keyword: &str, value: &str) -> Result<(), String> {
+        let value = unquote(value);
+        match keyword {
+            "hostname" => set_once(&mut self.host_name, value),
+            "user" => set_once(&mut self.user, value),
+            "port" => {
+                let port = value
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid Port '{value}'"))?;
+                self.port.get_or_insert(port);
+            }
+            "identityfile" => set_once(&mut self.identity_file, value),
+            "proxyjump" => set_once(&mut self.proxy_jump, value),
+            "stricthostkeychecking" => set_once(&mut self.strict_host_key_checking, value),
+            "userknownhostsfile" => {
+                // Only the first of several listed files is used
+                let first = value.split_whitespace().next().unwrap_or_default();
+                set_once(&mut self.user_known_hosts_file, first);
+            }
+            "localforward" => self.forwards.push(forward(ForwardingMode::Local, value)?),
+            "remoteforward" => self.forwards.push(forward(ForwardingMode::Remote, value)?),
+            "dynamicforward" => self
+                .forwards
+                .push(format!("{}{value}", ForwardingMode::Dynamic.spec_prefix())),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn into_config(self, host: &str) -> StunResult<Config> {
+        if self.forwards.is_empty() {
+            return Err(StunError::Config(format!(
+                "Host {host} has no LocalForward, RemoteForward or DynamicForward in ssh config"
+            )));
+        }
+
+        let host_key_policy = match self.strict_host_key_checking.as_deref() {
+            Some("yes") => HostKeyPolicy::Strict,
+            _ => HostKeyPolicy::AcceptNew,
+        };
+        let jump_hosts = match self.proxy_jump.as_deref() {
+            None | Some("none") => Vec::new(),
+            Some(chain) => chain
+                .split(',')
+                .map(parse_jump_host)
+                .collect::<StunResult<_>>()?,
+        };
+
+        let host_name = self
+            .host_name
+            .and_then(|value| expand_setting("HostName", &value, &[('h', host)]))
+            .unwrap_or_else(|| host.to_string());
+        let port = self.port.unwrap_or(22);
+        let local_user = std::env::var("USER").unwrap_or_default();
+        let user = self.user.unwrap_or_else(|| local_user.clone());
+        let home = home_dir().display().to_string();
+        let port_token = port.to_string();
+        let path_tokens = [
+            ('h', host_name.as_str()),
+            ('n', host),
+            ('p', port_token.as_str()),
+            ('r', user.as_str()),
+            ('u', local_user.as_str()),
+            ('d', home.as_str()),
+        ];
+        let expand_path = |keyword: &str, value: Option<String>| {
+            value
+                .and_then(|value| expand_setting(keyword, &value, &path_tokens))
+                .map(|path| expand_home(&path))
+        };
+        let key = expand_path("IdentityFile", self.identity_file);
+        let known_hosts = expand_path("UserKnownHostsFile", self.user_known_hosts_file);
+
+        let remote = RemoteConfig {
+            host: host_name,
+            port,
+            user,
+            key,
+            host_key_policy,
+            known_hosts,
+            jump_hosts,
+            ..RemoteConfig::default()
+        };
+
+        let config = Config {
+            mode: ForwardingMode::Local,
+            remote,
+            forwarding_list: self.forwards.into_iter().map(Into::into).collect(),
+            ..Config::default()
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Split `Keyword value` or `Keyword=value`, skipping blank lines and comments
+fn split_directive(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let (keyword, rest) = line.split_at(end);
+    let value = rest.trim_start().strip_prefix('=').unwrap_or(rest).trim();
+    Some((keyword, value))
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn set_once(slot: &mut Option<String>, value: &str) {
+    if slot.is_none() {
+        *slot = Some(value.to_string());
+    }
+}
+
+/// Expand the `%` tokens of a directive's value, or warn and skip the value when it
+/// uses a token that is not in `tokens`
+fn expand_setting(keyword: &str, value: &str, tokens: &[(char, &str)]) -> Option<String> {
+    match expand_tokens(value, tokens) {
+        Ok(expanded) => Some(expanded),
+        Err(token) => {
+            warn!(
+                "Ignoring {} {}: ssh token {} is not supported",
+                keyword, value, token
+            );
+            None
+        }
+    }
+}
+
+/// Replace `%%` and the `%` tokens in `tokens`; the first other token is returned as the error
+fn expand_tokens(value: &str, tokens: &[(char, &str)]) -> Result<String, String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some(token) => match tokens.iter().find(|(name, _)| *name == token) {
+                Some((_, replacement)) => expanded.push_str(replacement),
+                None => return Err(format!("%{token}")),
+            },
+            None => return Err("%".to_string()),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Whether a `Host` pattern list applies: some pattern matches and no negated one does
+fn host_matches(patterns: &str, host: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split_whitespace() {
+        match pattern.strip_prefix('!') {
+            Some(negated) if glob_match(negated, host) => return false,
+            Some(_) => {}
+            None => matched |= glob_match(pattern, host),
+        }
+    }
+    matched
+}
+
+/// ssh_config wildcard match supporting `*` and `?`
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// `[bind_address:]port host:hostport` to a prefixed forwarding spec
+fn forward(mode: ForwardingMode, value: &str) -> Result<String, String> {
+    let mut args = value.split_whitespace();
+    match (args.next(), args.next(), args.next()) {
+        (Some(bind), Some(target), None) => Ok(format!("{}{bind}:{target}", mode.spec_prefix())),
+        _ => Err(format!(
+            "expected '[bind_address:]port host:hostport', got '{value}'"
+        )),
+    }
+}
+
+/// `[user@]host[:port]` entry of a ProxyJump list
+fn parse_jump_host(entry: &str) -> StunResult<JumpHost> {
+    let entry = entry.trim();
+    let entry = entry.strip_prefix("ssh://").unwrap_or(entry);
+    let (user, address) = match entry.rsplit_once('@') {
+        Some((user, address)) => (Some(user.to_string()), address),
+        None => (None, entry),
+    };
+
+    let invalid = || StunError::Config(format!("Invalid ProxyJump host '{entry}'"));
+    let (host, port) = if let Some(rest) = address.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+        (host, rest.strip_prefix(':'))
+    } else {
+        match address.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        }
+    };
+    let port = match port {
+        Some(port) => port.parse::<u16>().map_err(|_| invalid())?,
+        None => 22,
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(JumpHost {
+        host: host.to_string(),
+        port,
+        user,
+        key: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SSH_CONFIG: &str = r#"
+# Defaults for the staging hosts
+Host staging-db !staging-old
+    HostName db.staging.internal
+    User deploy
+    Port 2222
+    IdentityFile "/keys/staging"
+    ProxyJump jump@bastion.example.com:2200,gateway
+    LocalForward 5432 localhost:5432
+    RemoteForward=127.0.0.1:9000 127.0.0.1:9000
+
+Host staging-*
+    User ignored
+    DynamicForward 1080
+    StrictHostKeyChecking yes
+
+Match host staging-db
+    LocalForward 6543 localhost:6543
+"#;
+
+    #[test]
+    fn test_parse_host_block() {
+        let config = parse(SSH_CONFIG, "staging-db").unwrap();
+
+        assert_eq!(config.remote.host, "db.staging.internal");
+        assert_eq!(config.remote.user, "deploy");
+        assert_eq!(config.remote.port, 2222);
+        assert_eq!(config.remote.key.as_deref(), Some("/keys/staging"));
+        assert_eq!(config.remote.host_key_policy, HostKeyPolicy::Strict);
+        assert_eq!(
+            config.remote.jump_hosts[0].destination(),
+            "jump@bastion.example.com:2200"
+        );
+        assert_eq!(config.remote.jump_hosts[1].destination(), "gateway");
+        assert_eq!(
+            config.forwarding_list,
+            vec![
+                "L:5432:localhost:5432".into(),
+                "R:127.0.0.1:9000:127.0.0.1:9000".into(),
+                "D:1080".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_host_patterns() {
+        assert!(host_matches("staging-*", "staging-db"));
+        assert!(host_matches("db?", "db1"));
+        assert!(!host_matches("staging-* !staging-old", "staging-old"));
+        assert!(!host_matches("prod-*", "staging-db"));
+
+        // staging-old only picks up the wildcard block's DynamicForward
+        let config = parse(SSH_CONFIG, "staging-old").unwrap();
+        assert_eq!(config.remote.host, "staging-old");
+        assert_eq!(config.forwarding_list, vec!["D:1080".into()]);
+
+        assert!(parse(SSH_CONFIG, "prod").is_err());
+    }
+
+    #[test]
+    fn test_tokens() {
+        let content = r#"
+Host web
+    HostName %h.example.com
+    User admin
+    Port 2200
+    IdentityFile /keys/%r@%h:%p-100%%
+    UserKnownHostsFile /hosts/%C
+    LocalForward 8080 localhost:80
+"#;
+        let config = parse(content, "web").unwrap();
+        assert_eq!(config.remote.host, "web.example.com");
+        assert_eq!(
+            config.remote.key.as_deref(),
+            Some("/keys/admin@web.example.com:2200-100%")
+        );
+        // %C is not supported, so ssh's default known_hosts is kept
+        assert_eq!(config.remote.known_hosts, None);
+
+        assert_eq!(expand_tokens("%n", &[('n', "web")]).unwrap(), "web");
+        assert_eq!(expand_tokens("%L", &[]).unwrap_err(), "%L");
+        assert_eq!(expand_tokens("trailing %", &[]).unwrap_err(), "%");
+    }
+}
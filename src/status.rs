@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::DEFAULT_REMOTE, forwarding::ForwardingSpec, health::TunnelHealth};
+
+/// Point-in-time snapshot of a managed tunnel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TunnelStatus {
+    /// Key the tunnel is tracked under
+    pub name: String,
+    /// Forwarding specification
+    pub spec: ForwardingSpec,
+    /// Remote the tunnel connects through
+    #[serde(default = "default_remote")]
+    pub remote: String,
+    /// `host:port` of the remote endpoint in use, which changes on failover
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Current health status
+    pub health: TunnelHealth,
+    /// PID of the SSH process, if one is running
+    pub pid: Option<u32>,
+    /// Seconds since the SSH process was last started
+    pub uptime_secs: Option<u64>,
+    /// Number of times the tunnel has been restarted
+    pub restart_count: u32,
+    /// Number of consecutive failed health checks
+    pub consecutive_failures: u32,
+    /// Most recent launch or health check error
+    pub last_error: Option<String>,
+    /// Seconds until the next scheduled restart attempt
+    pub next_restart_in_secs: Option<u64>,
+    /// Duration of the last successful health probe in milliseconds
+    pub last_probe_latency_ms: Option<u64>,
+    /// Labels from the tunnel's configuration entry
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Port range entry the tunnel was expanded from
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Port picked for an automatic (`auto`) bind port, once the tunnel was launched
+    #[serde(default)]
+    pub assigned_port: Option<u16>,
+}
+
+fn default_remote() -> String {
+    DEFAULT_REMOTE.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_json_round_trip() {
+        let status = TunnelStatus {
+            name: "8080:127.0.0.1:9000".to_string(),
+            spec: ForwardingSpec::parse("8080:127.0.0.1:9000").unwrap(),
+            remote: "staging".to_string(),
+            endpoint: Some("bastion-2.example.com:22".to_string()),
+            health: TunnelHealth::Healthy,
+            pid: Some(4242),
+            uptime_secs: Some(12),
+            restart_count: 1,
+            consecutive_failures: 0,
+            last_error: None,
+            next_restart_in_secs: None,
+            last_probe_latency_ms: Some(3),
+            tags: vec!["db".to_string()],
+            group: None,
+            assigned_port: None,
+        };
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["health"], "healthy");
+        assert_eq!(json["spec"]["bind_port"], 8080);
+
+        let decoded: TunnelStatus = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, status);
+    }
+}
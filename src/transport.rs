@@ -0,0 +1,120 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use tokio::{process::Child, task::JoinHandle};
+
+use crate::{
+    config::{Config, TransportKind},
+    error::StunResult,
+    forwarding::ForwardingSpec,
+    ssh::SshClient,
+};
+
+/// Backend that opens, monitors and closes forwards for [`TunnelManager`](crate::TunnelManager).
+///
+/// [`SshClient`] drives the system `ssh` binary; with the `native-ssh` feature,
+/// [`NativeTransport`](crate::native::NativeTransport) speaks SSH in-process.
+#[async_trait]
+pub trait TunnelTransport: Send + Sync {
+    /// Open a forward for `spec`
+    async fn open(&self, spec: &ForwardingSpec) -> StunResult<ForwardHandle>;
+
+    /// Check whether the forward behind `handle` is still running
+    async fn is_alive(&self, handle: &mut ForwardHandle) -> bool;
+
+    /// Close a forward previously returned by [`TunnelTransport::open`]
+    async fn close(&self, spec: &ForwardingSpec, handle: ForwardHandle) -> StunResult<()>;
+
+    /// Liveness of a connection shared by several forwards.
+    /// None when every forward has its own connection.
+    async fn connection_alive(&self) -> Option<bool> {
+        None
+    }
+
+    /// Re-establish the shared connection after it was lost
+    async fn reconnect(&self) -> StunResult<()> {
+        Ok(())
+    }
+
+    /// Check from the remote side that `host:port` accepts TCP connections
+    async fn probe_remote(&self, host: &str, port: u16) -> StunResult<bool>;
+
+    /// Release shared resources; called once when the manager stops
+    async fn shutdown(&self) {}
+}
+
+/// A running forward returned by [`TunnelTransport::open`]
+#[derive(Debug)]
+pub enum ForwardHandle {
+    /// Forward carried by its own `ssh` process
+    Process(Child),
+    /// Forward registered on a shared OpenSSH master connection
+    Multiplexed,
+    /// Forward served by an in-process task on a shared connection
+    Task(TaskHandle),
+}
+
+impl ForwardHandle {
+    /// PID of the process carrying this forward, if it has its own
+    pub fn pid(&self) -> Option<u32> {
+        match self {
+            ForwardHandle::Process(child) => child.id(),
+            ForwardHandle::Multiplexed | ForwardHandle::Task(_) => None,
+        }
+    }
+
+    /// Returns true if the forward dies together with the transport's shared connection
+    pub fn is_shared(&self) -> bool {
+        !matches!(self, ForwardHandle::Process(_))
+    }
+}
+
+/// Background task serving a forward
+#[derive(Debug)]
+pub struct TaskHandle {
+    task: JoinHandle<()>,
+}
+
+impl TaskHandle {
+    /// Wrap a task; connections it serves should end when it is aborted
+    pub fn new(task: JoinHandle<()>) -> Self {
+        Self { task }
+    }
+
+    /// Returns true once the task has exited
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Abort the task
+    pub fn cancel(self) {
+        self.task.abort();
+    }
+}
+
+/// Build one transport per configured remote, keyed by remote name
+pub fn build_transports(config: &Config) -> StunResult<HashMap<String, Arc<dyn TunnelTransport>>> {
+    config
+        .remote_names()
+        .into_iter()
+        .map(|name| {
+            let transport = build_transport(&config.for_remote(&name)?)?;
+            Ok((name, transport))
+        })
+        .collect()
+}
+
+/// Build the transport selected by `remote.transport`
+pub fn build_transport(config: &Config) -> StunResult<Arc<dyn TunnelTransport>> {
+    match config.remote.transport {
+        TransportKind::Process => Ok(Arc::new(SshClient::new(config.clone()))),
+        #[cfg(feature = "native-ssh")]
+        TransportKind::Native => Ok(Arc::new(crate::native::NativeTransport::new(
+            config.clone(),
+        ))),
+        #[cfg(not(feature = "native-ssh"))]
+        TransportKind::Native => Err(crate::error::StunError::Config(
+            "The native transport requires building stun with the `native-ssh` feature".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{StunError, StunResult};
+
+/// How serious a validation issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The configuration cannot be used
+    Error,
+    /// The configuration loads, but a tunnel is likely to fail
+    Warning,
+}
+
+/// One problem found in a configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    /// Whether the issue blocks loading
+    pub severity: Severity,
+    /// Location in the document, e.g. `forwarding_list[2]` or `remotes.prod.host`;
+    /// empty when the issue concerns the whole file
+    pub path: String,
+    /// 1-based line in the file, when the parser reported one
+    pub line: Option<usize>,
+    /// 1-based column in the file, when the parser reported one
+    pub column: Option<usize>,
+    /// What is wrong, without the location
+    pub message: String,
+}
+
+impl ValidationIssue {
+    /// An error at `path`
+    pub fn error(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            path: path.into(),
+            line: None,
+            column: None,
+            message: message.into(),
+        }
+    }
+
+    /// A warning at `path`
+    pub fn warning(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(path, message)
+        }
+    }
+
+    /// Attach a 1-based line and column
+    pub fn at(mut self, line: usize, column: usize) -> Self {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "line {line}, column {column}: ")?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Every issue found while checking a configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Issues in the order they were found
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Record an error at `path`
+    pub fn error(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ValidationIssue::error(path, message));
+    }
+
+    /// Record a warning at `path`
+    pub fn warning(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ValidationIssue::warning(path, message));
+    }
+
+    /// Add the issues of another report
+    pub fn extend(&mut self, other: ValidationReport) {
+        self.issues.extend(other.issues);
+    }
+
+    /// Issues that make the configuration unusable
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    /// Issues that do not prevent loading
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    /// Whether any issue is an error
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// `Ok` without errors, otherwise a [`StunError::Config`] listing all of them
+    pub fn into_result(self) -> StunResult<()> {
+        let errors: Vec<String> = self.errors().map(ToString::to_string).collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(StunError::Config(errors.join("; ")))
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            let severity = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            writeln!(f, "{severity}: {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_report() {
+        let mut report = ValidationReport::default();
+        report.warning("remotes.prod.host", "host is empty");
+        assert!(!report.has_errors());
+        assert!(report.clone().into_result().is_ok());
+
+        report.error("forwarding_list[1]", "invalid port");
+        report
+            .issues
+            .push(ValidationIssue::error("", "expected value").at(3, 7));
+        assert_eq!(report.errors().count(), 2);
+        assert_eq!(report.warnings().count(), 1);
+
+        assert_eq!(
+            report.to_string(),
+            "warning: remotes.prod.host: host is empty\n\
+             error: forwarding_list[1]: invalid port\n\
+             error: line 3, column 7: expected value\n"
+        );
+        match report.into_result() {
+            Err(StunError::Config(message)) => assert_eq!(
+                message,
+                "forwarding_list[1]: invalid port; line 3, column 7: expected value"
+            ),
+            other => panic!("expected a config error, got {other:?}"),
+        }
+    }
+}
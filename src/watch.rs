@@ -0,0 +1,191 @@
+use std::{sync::Arc, time::Duration};
+
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{Config, ConfigSource},
+    error::StunResult,
+    manager::{ReloadSummary, TunnelManager},
+};
+
+/// How often the configuration file is checked for changes by default
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Reloads a running [`TunnelManager`] when its configuration changes.
+///
+/// The source is re-read every poll interval, so edits to included fragments are
+/// picked up as well, and immediately on SIGHUP. A configuration that fails to load
+/// or validate is reported and the running tunnels are left as they are.
+pub struct ConfigWatcher {
+    source: ConfigSource,
+    manager: Arc<TunnelManager>,
+    applied: Config,
+    poll_interval: Option<Duration>,
+    last_error: Option<String>,
+}
+
+impl ConfigWatcher {
+    /// Watch `source`, whose contents `manager` is currently running as `applied`
+    pub fn new(source: ConfigSource, manager: Arc<TunnelManager>, applied: Config) -> Self {
+        Self {
+            source,
+            manager,
+            applied,
+            poll_interval: Some(DEFAULT_POLL_INTERVAL),
+            last_error: None,
+        }
+    }
+
+    /// Check the file every `interval`; None only reloads on SIGHUP
+    pub fn with_poll_interval(mut self, interval: Option<Duration>) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Watch until the manager stops
+    pub async fn run(mut self) -> StunResult<()> {
+        let mut hangup = Hangup::new()?;
+        let mut poll = self.poll_interval.map(tokio::time::interval);
+
+        loop {
+            let forced = tokio::select! {
+                _ = tick(&mut poll) => false,
+                _ = hangup.recv() => true,
+            };
+
+            if !self.manager.is_running() {
+                return Ok(());
+            }
+            if forced {
+                info!("Received SIGHUP, reloading {}", self.source.path.display());
+            }
+            self.check(forced).await;
+        }
+    }
+
+    /// Load the source and apply it if it differs from the running configuration,
+    /// or unconditionally when `forced`
+    pub async fn check(&mut self, forced: bool) -> Option<ReloadSummary> {
+        let config = match self.source.load() {
+            Ok(config) => config,
+            Err(e) => {
+                // A half-written file fails on every poll until the editor finishes
+                let message = e.to_string();
+                if forced || self.last_error.as_ref() != Some(&message) {
+                    warn!("Not reloading {}: {}", self.source.path.display(), message);
+                }
+                self.last_error = Some(message);
+                return None;
+            }
+        };
+        self.last_error = None;
+
+        if !forced && config == self.applied {
+            return None;
+        }
+
+        match self.manager.reload(config.clone()).await {
+            Ok(summary) => {
+                info!(
+                    "Reloaded {}: {} added, {} removed, {} restarted",
+                    self.source.path.display(),
+                    summary.added.len(),
+                    summary.removed.len(),
+                    summary.restarted.len()
+                );
+                debug!("Reload summary: {:?}", summary);
+                self.applied = config;
+                Some(summary)
+            }
+            Err(e) => {
+                warn!("Failed to apply {}: {}", self.source.path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Wait for the next poll, or forever when polling is off
+async fn tick(poll: &mut Option<tokio::time::Interval>) {
+    match poll {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// SIGHUP listener; never fires on platforms without the signal
+struct Hangup {
+    #[cfg(unix)]
+    signal: tokio::signal::unix::Signal,
+}
+
+impl Hangup {
+    fn new() -> StunResult<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if self.signal.recv().await.is_some() {
+            return;
+        }
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "mode": "local",
+        "remote": {"host": "127.0.0.1", "port": 1, "user": "testuser"},
+        "forwarding_list": [FORWARDS],
+        "timeout": 1
+    }"#;
+
+    #[tokio::test]
+    async fn test_reload_on_change() {
+        let path = std::env::temp_dir().join(format!("stun-watch-{}.json", std::process::id()));
+        let write = |forwards: &str| std::fs::write(&path, CONFIG.replace("FORWARDS", forwards));
+        write(r#""18380:127.0.0.1:8080""#).unwrap();
+
+        let source = ConfigSource::new(&path);
+        let config = source.load().unwrap();
+        let mut manager = TunnelManager::new(config.clone()).unwrap();
+        let handle = manager.start_background().await.unwrap();
+        let manager = Arc::new(manager);
+        let mut watcher = ConfigWatcher::new(source, Arc::clone(&manager), config);
+
+        // Unchanged file: nothing to do
+        assert_eq!(watcher.check(false).await, None);
+
+        write(r#""18380:127.0.0.1:8080", "18381:127.0.0.1:8081""#).unwrap();
+        let summary = watcher.check(false).await.unwrap();
+        assert_eq!(summary.added, ["18381:127.0.0.1:8081"]);
+        assert!(summary.removed.is_empty() && summary.restarted.is_empty());
+
+        // An invalid file keeps the running tunnels
+        write(r#""not a spec""#).unwrap();
+        assert_eq!(watcher.check(false).await, None);
+        assert_eq!(manager.get_status().await.len(), 2);
+
+        // SIGHUP reloads even when nothing changed
+        write(r#""18380:127.0.0.1:8080", "18381:127.0.0.1:8081""#).unwrap();
+        assert_eq!(watcher.check(true).await, Some(ReloadSummary::default()));
+
+        let watcher = watcher.with_poll_interval(Some(Duration::from_millis(20)));
+        let watch_task = tokio::spawn(watcher.run());
+        manager.stop().await.unwrap();
+        handle.await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let stopped = tokio::time::timeout(Duration::from_secs(5), watch_task).await;
+        assert!(stopped.unwrap().unwrap().is_ok());
+    }
+}